@@ -0,0 +1,292 @@
+//! The build manifest (`<outdir>/.cuteness-cache`) used to skip re-rendering pages whose inputs haven't changed, and the list of files written by a static build (`<outdir>/.cuteness-output`).
+
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use hashbrown::HashMap;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use walkdir::WalkDir;
+
+/// Name of the manifest file, stored in the output directory.
+pub const MANIFEST_FILE: &str = ".cuteness-cache";
+
+/// Name of the list of files written by a static build, stored in the output directory. Only these files are ever removed from it.
+pub const OUTPUT_LIST: &str = ".cuteness-output";
+
+/// Hashes of every input used in the last build. `T` is whatever needs to be remembered about a page to skip rendering it.
+#[derive(Serialize, Deserialize)]
+pub struct Manifest<T> {
+    /// Version of cuteness that wrote the manifest, a different version invalidates everything.
+    version: String,
+    /// Hash of the inputs shared by every page (configuration, summary and templates).
+    global: String,
+    pages: HashMap<PathBuf, Entry<T>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    /// Hash of the source file, front-matter included.
+    hash: String,
+    data: T,
+}
+
+impl<T> Default for Manifest<T> {
+    fn default() -> Self {
+        Manifest {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            global: String::new(),
+            pages: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Clone> Manifest<T> {
+    /// Loads the manifest from `outdir`. A missing, unreadable or outdated manifest is just an empty one, so everything gets rebuilt.
+    pub fn load(outdir: &Path) -> Self {
+        fs::read(outdir.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_slice::<Self>(&content).ok())
+            .filter(|manifest| manifest.version == env!("CARGO_PKG_VERSION"))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, outdir: &Path) -> Result<()> {
+        fs::write(
+            outdir.join(MANIFEST_FILE),
+            serde_json::to_vec(self).context("Couldn't serialize build manifest")?,
+        )
+        .with_context(|| {
+            format!(
+                "Couldn't write build manifest `{}`",
+                outdir.join(MANIFEST_FILE).display()
+            )
+        })
+    }
+
+    /// Updates the hash of the shared inputs. If it changed, every page is invalidated.
+    pub fn set_global(&mut self, hash: String) {
+        if self.global != hash {
+            self.pages.clear();
+            self.global = hash;
+        }
+    }
+
+    /// Returns the data stored for `path` if its source still hashes to `hash`.
+    pub fn get(&self, path: &Path, hash: &str) -> Option<T> {
+        self.pages
+            .get(path)
+            .filter(|entry| entry.hash == hash)
+            .map(|entry| entry.data.clone())
+    }
+
+    pub fn insert(&mut self, path: PathBuf, hash: String, data: T) {
+        self.pages.insert(path, Entry { hash, data });
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.pages.remove(path);
+    }
+
+    /// Forgets every page for which `keep` returns `false` (e.g. deleted source files).
+    pub fn retain(&mut self, mut keep: impl FnMut(&Path) -> bool) {
+        self.pages.retain(|path, _| keep(path));
+    }
+}
+
+/// Hashes the files in `paths` (directories are walked in a stable order). Missing files are skipped, so creating them still changes the hash.
+pub fn hash_files<P: AsRef<Path>>(paths: &[P]) -> Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    for path in paths {
+        for entry in WalkDir::new(path)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+        {
+            hasher.update(entry.path().to_string_lossy().as_bytes());
+            hasher.update(
+                &fs::read(entry.path())
+                    .with_context(|| format!("Couldn't read `{}`", entry.path().display()))?,
+            );
+        }
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// The files listed in the output list of `outdir`, relative to it. Empty if there's no list; anything that isn't a path inside `outdir` is left out.
+pub fn load_output_list(outdir: &Path) -> Vec<PathBuf> {
+    fs::read_to_string(outdir.join(OUTPUT_LIST))
+        .unwrap_or_default()
+        .lines()
+        .map(PathBuf::from)
+        .filter(|path| {
+            path.components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        })
+        .collect()
+}
+
+/// Writes the output list of `outdir`, `files` being relative to it.
+pub fn save_output_list(outdir: &Path, files: &[PathBuf]) -> Result<()> {
+    let mut list = String::new();
+    for file in files {
+        list.push_str(&file.to_string_lossy());
+        list.push('\n');
+    }
+    fs::write(outdir.join(OUTPUT_LIST), list).with_context(|| {
+        format!(
+            "Couldn't write the list of generated files `{}`",
+            outdir.join(OUTPUT_LIST).display()
+        )
+    })
+}
+
+/// Removes `files` (relative to `outdir`) and the directories they leave empty.
+pub fn remove_outputs(outdir: &Path, files: &[PathBuf]) -> Result<()> {
+    for file in files {
+        let path = outdir.join(file);
+        if !path.is_file() {
+            continue;
+        }
+        fs::remove_file(&path).with_context(|| format!("Couldn't remove `{}`", path.display()))?;
+
+        let mut parent = file.parent();
+        while let Some(dir) = parent.filter(|dir| !dir.as_os_str().is_empty()) {
+            // Fails if it isn't empty
+            if fs::remove_dir(outdir.join(dir)).is_err() {
+                break;
+            }
+            parent = dir.parent();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cuteness-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn manifest() -> Manifest<String> {
+        let mut manifest = Manifest::default();
+        manifest.set_global("global".to_string());
+        manifest.insert(PathBuf::from("src/a.md"), "a1".to_string(), "A".to_string());
+        manifest.insert(PathBuf::from("src/b.md"), "b1".to_string(), "B".to_string());
+        manifest
+    }
+
+    #[test]
+    fn changed_pages_are_invalidated() {
+        let mut manifest = manifest();
+        assert_eq!(
+            manifest.get(Path::new("src/a.md"), "a1").as_deref(),
+            Some("A")
+        );
+        assert_eq!(manifest.get(Path::new("src/a.md"), "a2"), None);
+        assert_eq!(manifest.get(Path::new("src/c.md"), "a1"), None);
+
+        manifest.retain(|path| path != Path::new("src/b.md"));
+        assert_eq!(manifest.get(Path::new("src/b.md"), "b1"), None);
+    }
+
+    #[test]
+    fn global_changes_invalidate_everything() {
+        let mut manifest = manifest();
+        manifest.set_global("global".to_string());
+        assert!(manifest.get(Path::new("src/a.md"), "a1").is_some());
+
+        manifest.set_global("changed".to_string());
+        assert_eq!(manifest.get(Path::new("src/a.md"), "a1"), None);
+        assert_eq!(manifest.get(Path::new("src/b.md"), "b1"), None);
+    }
+
+    #[test]
+    fn saved_manifest() {
+        let outdir = temp_dir("manifest");
+        manifest().save(&outdir).unwrap();
+        let loaded: Manifest<String> = Manifest::load(&outdir);
+        assert_eq!(
+            loaded.get(Path::new("src/a.md"), "a1").as_deref(),
+            Some("A")
+        );
+
+        // Another version of cuteness
+        let outdated = fs::read_to_string(outdir.join(MANIFEST_FILE))
+            .unwrap()
+            .replace(env!("CARGO_PKG_VERSION"), "0.0.0");
+        fs::write(outdir.join(MANIFEST_FILE), outdated).unwrap();
+        let loaded: Manifest<String> = Manifest::load(&outdir);
+        assert_eq!(loaded.get(Path::new("src/a.md"), "a1"), None);
+
+        fs::write(outdir.join(MANIFEST_FILE), "not json").unwrap();
+        let loaded: Manifest<String> = Manifest::load(&outdir);
+        assert_eq!(loaded.get(Path::new("src/a.md"), "a1"), None);
+
+        fs::remove_dir_all(&outdir).unwrap();
+    }
+
+    #[test]
+    fn hashing_files() {
+        let dir = temp_dir("hash");
+        fs::create_dir_all(dir.join("templates")).unwrap();
+        fs::write(dir.join("templates/page.hbs"), "a").unwrap();
+        let paths = [dir.join("config.toml"), dir.join("templates")];
+
+        let hash = hash_files(&paths).unwrap();
+        assert_eq!(hash_files(&paths).unwrap(), hash);
+        fs::write(dir.join("templates/page.hbs"), "b").unwrap();
+        let changed = hash_files(&paths).unwrap();
+        assert_ne!(changed, hash);
+        // Creating a missing file changes it too
+        fs::write(dir.join("config.toml"), "").unwrap();
+        assert_ne!(hash_files(&paths).unwrap(), changed);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn output_list() {
+        let outdir = temp_dir("output");
+        fs::create_dir_all(outdir.join("guide/intro")).unwrap();
+        for file in ["guide/intro/index.html", "index.html", "mine.txt"] {
+            fs::write(outdir.join(file), "").unwrap();
+        }
+
+        let files = [
+            PathBuf::from("guide/intro/index.html"),
+            PathBuf::from("index.html"),
+        ];
+        save_output_list(&outdir, &files).unwrap();
+        assert_eq!(load_output_list(&outdir), files);
+
+        remove_outputs(&outdir, &files).unwrap();
+        assert!(!outdir.join("guide").exists());
+        assert!(!outdir.join("index.html").exists());
+        assert!(outdir.join("mine.txt").exists());
+
+        fs::remove_dir_all(&outdir).unwrap();
+    }
+
+    #[test]
+    fn output_list_stays_inside_outdir() {
+        let outdir = temp_dir("escape");
+        fs::write(
+            outdir.join(OUTPUT_LIST),
+            "../outside\n/etc/passwd\nok.html\n",
+        )
+        .unwrap();
+        assert_eq!(load_output_list(&outdir), [PathBuf::from("ok.html")]);
+        assert!(load_output_list(&outdir.join("missing")).is_empty());
+
+        fs::remove_dir_all(&outdir).unwrap();
+    }
+}
@@ -0,0 +1,115 @@
+//! External preprocessors: commands declared in `[preprocessors]` that get every page as JSON on their standard input and write it back, modified, on their standard output.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// What an external preprocessor gets on its standard input.
+#[derive(Serialize, Debug)]
+pub struct Input<'a> {
+    /// The page's front-matter
+    pub page: &'a Value,
+    /// The page's Markdown, without the front-matter
+    pub content: &'a str,
+    /// Path of the page, e.g. `src/guide/intro.md`
+    pub path: &'a str,
+    /// The whole `cuteconfig.toml`
+    pub outer: &'a Value,
+}
+
+/// `Input` plus the preprocessor's own options in `[preprocessors]`.
+#[derive(Serialize, Debug)]
+struct Request<'a> {
+    #[serde(flatten)]
+    input: &'a Input<'a>,
+    options: &'a toml::Table,
+}
+
+/// What an external preprocessor writes to its standard output.
+#[derive(Deserialize, Debug)]
+struct Output {
+    /// The page's new Markdown
+    content: String,
+}
+
+pub struct External {
+    name: String,
+    program: String,
+    args: Vec<String>,
+    /// Every option but `command` and `enabled`
+    options: toml::Table,
+}
+
+impl External {
+    /// `options` is the preprocessor's table in `[preprocessors]`, `command` is the command line to run, split like a shell would (e.g. `"python3 'my tools/glossary.py'"`).
+    pub fn new(name: &str, options: &toml::Table) -> Result<Self> {
+        let Some(toml::Value::String(command)) = options.get("command") else {
+            bail!("`command` must be a string, e.g. \"python3 tools/glossary.py\"");
+        };
+        // Split like a shell would, so quoted arguments and paths with spaces work
+        let mut words = shell_words::split(command)
+            .with_context(|| format!("Couldn't parse `command`: {command}"))?
+            .into_iter();
+        let Some(program) = words.next() else {
+            bail!("`command` can't be empty");
+        };
+
+        let mut options = options.clone();
+        options.remove("command");
+        options.remove("enabled");
+        Ok(External {
+            name: name.to_string(),
+            program,
+            args: words.collect(),
+            options,
+        })
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Runs the preprocessor on a page, returning its new Markdown.
+    pub fn run(&self, input: &Input) -> Result<String> {
+        let input = serde_json::to_vec(&Request {
+            input,
+            options: &self.options,
+        })
+        .context("Couldn't serialize the page")?;
+
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| format!("Couldn't run preprocessor `{}`", self.name))?;
+
+        // Written from another thread, so a preprocessor that writes before reading everything can't block us. Errors are ignored: it may exit without reading the whole page, and if it failed, its exit status says so.
+        let mut stdin = child.stdin.take().unwrap();
+        let output = std::thread::scope(|scope| {
+            scope.spawn(move || {
+                let _ = stdin.write_all(&input);
+            });
+            child.wait_with_output()
+        })
+        .with_context(|| format!("Preprocessor `{}` failed", self.name))?;
+        if !output.status.success() {
+            bail!("Preprocessor `{}` exited with {}", self.name, output.status);
+        }
+
+        let output: Output = serde_json::from_slice(&output.stdout).with_context(|| {
+            format!(
+                "Preprocessor `{}` should write `{{\"content\": \"...\"}}` to its standard output",
+                self.name
+            )
+        })?;
+        Ok(output.content)
+    }
+}
@@ -0,0 +1,241 @@
+//! Build-time syntax highlighting of fenced code blocks, configured with `[highlighting]` in `cuteconfig.toml`.
+
+use std::{fmt::Write, ops::RangeInclusive};
+
+use anyhow::{bail, Context, Result};
+use hashbrown::HashSet;
+use pulldown_cmark::escape::escape_html;
+use serde::{Deserialize, Serialize};
+use syntect::{
+    highlighting::ThemeSet,
+    html::{css_for_theme_with_class_style, line_tokens_to_classed_spans, ClassStyle},
+    parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet},
+};
+
+/// Name of the generated theme stylesheet, in the output's `styles` directory.
+pub const STYLESHEET: &str = "highlight.css";
+
+/// Every class is prefixed so the theme doesn't clash with other styles.
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// `[highlighting]` in `cuteconfig.toml`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HighlightConfig {
+    /// One of the themes bundled with cuteness (e.g. `InspiredGitHub`, `base16-ocean.dark`, `Solarized (light)`)
+    #[serde(default = "default_theme")]
+    pub theme: String,
+    /// Number every line of every code block
+    #[serde(default)]
+    pub line_numbers: bool,
+    /// Only highlight these languages (e.g. `["rust", "python"]`), every language if it's empty
+    #[serde(default)]
+    pub languages: Vec<String>,
+}
+
+impl Default for HighlightConfig {
+    fn default() -> Self {
+        HighlightConfig {
+            theme: default_theme(),
+            line_numbers: false,
+            languages: Vec::new(),
+        }
+    }
+}
+
+#[inline(always)]
+fn default_theme() -> String {
+    "InspiredGitHub".to_string()
+}
+
+pub struct Highlighter {
+    syntaxes: SyntaxSet,
+    /// The theme's CSS, written to `styles/highlight.css`
+    css: String,
+    line_numbers: bool,
+    /// Names of the allowed syntaxes, `None` if every one is allowed
+    allowed: Option<HashSet<String>>,
+}
+
+impl Highlighter {
+    pub fn new(config: &HighlightConfig) -> Result<Self> {
+        let syntaxes = SyntaxSet::load_defaults_newlines();
+        let mut themes = ThemeSet::load_defaults().themes;
+        let Some(theme) = themes.remove(&config.theme) else {
+            let mut available: Vec<&String> = themes.keys().collect();
+            available.sort();
+            bail!(
+                "Unknown syntax highlighting theme `{}` (available themes: `{}`)",
+                config.theme,
+                available
+                    .iter()
+                    .map(|theme| theme.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("`, `")
+            );
+        };
+
+        let allowed = if config.languages.is_empty() {
+            None
+        } else {
+            let mut allowed = HashSet::new();
+            for language in &config.languages {
+                let Some(syntax) = syntaxes.find_syntax_by_token(language) else {
+                    bail!("Unknown language `{language}` in `[highlighting] languages`");
+                };
+                allowed.insert(syntax.name.clone());
+            }
+            Some(allowed)
+        };
+
+        Ok(Highlighter {
+            css: css_for_theme_with_class_style(&theme, CLASS_STYLE)
+                .context("Couldn't generate the syntax highlighting stylesheet")?,
+            syntaxes,
+            line_numbers: config.line_numbers,
+            allowed,
+        })
+    }
+
+    #[inline]
+    pub fn css(&self) -> &str {
+        &self.css
+    }
+
+    /// Renders a code block. `info` is the fence's info string: the language and optionally the lines to highlight, e.g. `rust {3,5-7}`.
+    pub fn highlight(&self, code: &str, info: &str) -> String {
+        let (language, marked) = parse_info(info);
+        let syntax = self
+            .syntaxes
+            .find_syntax_by_token(language)
+            .filter(|syntax| {
+                self.allowed
+                    .as_ref()
+                    .is_none_or(|allowed| allowed.contains(&syntax.name))
+            });
+
+        let mut html = String::from("<pre class=\"hl-code");
+        if self.line_numbers {
+            html.push_str(" line-numbers");
+        }
+        html.push_str("\"><code");
+        if !language.is_empty() {
+            html.push_str(" class=\"language-");
+            escape_html(&mut html, language).unwrap();
+            html.push('"');
+        }
+        html.push('>');
+
+        let lines = match syntax {
+            Some(syntax) => self.highlight_lines(code, syntax),
+            None => None,
+        }
+        .unwrap_or_else(|| plain_lines(code));
+
+        for (number, line) in (1..).zip(lines) {
+            html.push_str("<span class=\"line");
+            if marked.iter().any(|range| range.contains(&number)) {
+                html.push_str(" highlighted");
+            }
+            html.push_str("\">");
+            html.push_str(&line);
+            html.push_str("</span>\n");
+        }
+
+        html.push_str("</code></pre>\n");
+        html
+    }
+
+    /// Every line of `code` as HTML, each one closing the spans it opens so lines can be wrapped. `None` if the syntax definition fails to parse the code.
+    fn highlight_lines(&self, code: &str, syntax: &SyntaxReference) -> Option<Vec<String>> {
+        let mut state = ParseState::new(syntax);
+        let mut stack = ScopeStack::new();
+        let mut lines = Vec::new();
+
+        for line in code.split_inclusive('\n') {
+            let mut html = String::new();
+            // Scopes still open from the previous line
+            for scope in stack.as_slice() {
+                html.push_str("<span class=\"");
+                for (i, atom) in scope.build_string().split('.').enumerate() {
+                    if i != 0 {
+                        html.push(' ');
+                    }
+                    write!(html, "hl-{atom}").unwrap();
+                }
+                html.push_str("\">");
+            }
+
+            let ops = state.parse_line(line, &self.syntaxes).ok()?;
+            let (spans, _) =
+                line_tokens_to_classed_spans(line, &ops, CLASS_STYLE, &mut stack).ok()?;
+            html.push_str(&spans);
+            html.push_str(&"</span>".repeat(stack.len()));
+
+            // The newline goes between the lines, not inside them
+            if let Some(newline) = html.rfind('\n') {
+                html.remove(newline);
+            }
+            lines.push(html);
+        }
+        Some(lines)
+    }
+}
+
+fn plain_lines(code: &str) -> Vec<String> {
+    code.lines()
+        .map(|line| {
+            let mut html = String::new();
+            escape_html(&mut html, line).unwrap();
+            html
+        })
+        .collect()
+}
+
+/// Splits `rust {3,5-7}` into the language and the lines to highlight.
+fn parse_info(info: &str) -> (&str, Vec<RangeInclusive<usize>>) {
+    let (language, attributes) = match info.find('{') {
+        Some(start) => (&info[..start], &info[start + 1..]),
+        None => (info, ""),
+    };
+    let language = language.split_whitespace().next().unwrap_or_default();
+
+    let mut marked = Vec::new();
+    for range in attributes.trim_end().trim_end_matches('}').split(',') {
+        let range = range.trim();
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        if let (Ok(start), Ok(end)) = (start.trim().parse(), end.trim().parse()) {
+            marked.push(start..=end);
+        }
+    }
+    (language, marked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_info_language() {
+        assert_eq!(parse_info("rust"), ("rust", vec![]));
+        assert_eq!(parse_info("rust ignore"), ("rust", vec![]));
+        assert_eq!(parse_info(""), ("", vec![]));
+    }
+
+    #[test]
+    fn parse_info_lines() {
+        assert_eq!(parse_info("rust {3}"), ("rust", vec![3..=3]));
+        assert_eq!(parse_info("rust{3,5-7}"), ("rust", vec![3..=3, 5..=7]));
+        assert_eq!(
+            parse_info("python { 1 , 2 - 4 } "),
+            ("python", vec![1..=1, 2..=4])
+        );
+        assert_eq!(parse_info("{2}"), ("", vec![2..=2]));
+    }
+
+    #[test]
+    fn parse_info_invalid_lines() {
+        assert_eq!(parse_info("rust {a,3}"), ("rust", vec![3..=3]));
+        assert_eq!(parse_info("rust {}"), ("rust", vec![]));
+        assert_eq!(parse_info("rust {1-}"), ("rust", vec![]));
+    }
+}
@@ -1,7 +1,11 @@
 //! ***Cuteness*** is a static site generator. It generates a [Rocket](https://rocket.rs) web-server and builds the Markdown[^4] source files. It was created to offer extreme configuration, and that's mainly what we're going to talk about here.
 //!
 //! * [`cuteconfig.toml`](#cuteconfig)
+//!     * [`[routing]`](#config.routing)
 //!     * [`[misc]`](#config.misc)
+//!     * [`[highlighting]`](#config.highlighting)
+//!     * [`[preprocessors]`](#config.preprocessors)
+//!     * [`[validation]`](#config.validation)
 //!     * [`[config]`](#config.config)
 //! * [The front-matter](#frontmatter)
 //!     * [Example](#frontmatter.example)
@@ -11,19 +15,25 @@
 //!     * [`{{outer.*}}`](#templating.outer)
 //!         * [Example](#templating.outer.example)
 //! * [Source files](#sourcefiles)
+//! * [`SUMMARY.toml`](#summary)
 //! * [Subcommands](#subcommands)
 //!     * [`init`](#subcommands.init)
 //!     * [`build`](#subcommands.build)
+//!     * [`serve`](#subcommands.serve)
 //!     * [`setup`](#subcommands.setup)
 //!     * [`update`](#subcommands.update)
+//!     * [`theme`](#subcommands.theme)
+//!     * [`doctor`](#subcommands.doctor)
 //!     * [`clean`](#subcommands.clean)
 //!     * [`uninstall`](#subcommands.uninstall)
 //!     * [`help`](#subcommands.help)
 //! * [Styles](#styles)
 //!     * [Using Sass](#styles.sass)
 //!     * [Not using Sass](#styles.css)
+//! * [Templates and themes](#templates)
 //! * [Routing](#routing)
 //! * [Preprocessors](#preprocessors)
+//!     * [External preprocessors](#preprocessors.external)
 //!
 //! ## `cuteconfig.toml` <a name="cuteconfig"></a>
 //!
@@ -32,22 +42,97 @@
 //!
 //! ```toml
 //! # cuteconfig.default.toml
+//! [routing]
+//! backend = "rocket" # Web-server generated in `<outdir>/routing` ("rocket", "axum" or "go")
+//! # proxies = ["nginx", "caddy"] # Reverse-proxy configurations to generate in `<outdir>/proxy`
+//! # proxy_dir = "proxy" # Where to write them (default: `<outdir>/proxy`, required with `--target static`)
+//! # Only used by the "go" backend
+//! init_behaviour = "fmt.Printf(\"Starting webserver at port 8080\")"
+//! fail_behaviour = "log.Fatal(err)"
+//! imports = ["fmt", "log"]
+//!
 //! [misc]
-//! latex = true # Add KaTeX support
+//! latex = true # Render $...$ and $$...$$ math to MathML
 //! html_lang = "en" # HTML Language
 //! syntax_highlighting = true
+//! search = true # Generate a search index and show a search box
+//! # theme = "my-theme" # Theme installed in the configuration directory
+//! # template_version = "v0.8.1" # Tag, branch or commit of the templates (default: "main")
+//!
+//! [highlighting] # Only used with `syntax_highlighting = true`
+//! theme = "InspiredGitHub" # Theme written to `styles/highlight.css`
+//! line_numbers = false # Number the lines of every code block
+//! languages = [] # Only highlight these languages (e.g. ["rust", "python"]), every language if it's empty
+//!
+//! [preprocessors] # Run in this order, `false` disables one
+//! curly_quotes = { quotes = "auto" } # "auto" follows `html_lang`, or e.g. "«»" or "„“‚‘"
+//! emojis = true
+//! # glossary = { command = "python3 tools/glossary.py" } # External preprocessor, run on the Markdown of every page
+//!
+//! [validation]
+//! dangling_entries = "warning" # `SUMMARY.toml` entries without a page ("error", "warning" or "ignore")
+//! orphan_pages = "warning" # Pages missing from `SUMMARY.toml` ("error", "warning" or "ignore")
 //!
 //! [config]
 //! # Write here your custom templates!
 //! ```
 //!
+//! ### `[routing]` <a name="config.routing"></a>
+//!
+//! This section configures the web-server generated in `<outdir>/routing` (see [*Routing*](#routing)).
+//!
+//! * `backend`: `"rocket"` (default) generates a [Rocket](https://rocket.rs) project, run with `cargo run --manifest-path <outdir>/routing/Cargo.toml`. `"axum"` generates an [Axum](https://github.com/tokio-rs/axum) project from `routing-axum/`, which builds with the stable toolchain, run the same way. `"go"` generates a single `<outdir>/routing/main.go` from `routing.go.hbs`, run with `go run <outdir>/routing/main.go`. Switching backends removes the files of the previous one.
+//! * `proxies`: Generates configurations to serve the pages with a reverse-proxy instead of the generated web-server: `"nginx"` writes a `server` block to `<outdir>/proxy/nginx.conf`, and `"caddy"` writes `<outdir>/proxy/Caddyfile`. They contain paths of the server, so a static site's output directory, which gets published, has no default: they're only written with `--target static` if `proxy_dir` is set. Both serve `introduction.html` at `/introduction`, `404.html` (from `src/404.md`) for missing pages, and cache the styles for a week.
+//! * `proxy_dir`: The directory the reverse-proxy configurations are written to, relative to the project's root. *(default: `<outdir>/proxy`)*
+//! * `init_behaviour` *(go)*: Go code run before the server starts.
+//! * `fail_behaviour` *(go)*: Go code run if the server fails, the error is in `err` (default: `panic(err)`).
+//! * `imports` *(go)*: Go packages used by `init_behaviour` and `fail_behaviour`.
+//!
 //! ### `[misc]` <a name="config.misc"></a>
 //!
 //! This section handles miscellaneous settings, usually related to preprocessors and very case-specific tools.
 //!
-//! * `latex`: Enables LaTeX[^1] equations.
+//! * `latex`: Renders LaTeX[^1] equations, `$...$` inline and `$$...$$` as a block, to MathML while building, so pages don't need any JavaScript. Formulas in code and HTML are left as they are, and so are prices like `$5 or $10` (an inline formula can't start or end with a space, or be followed by a digit); a literal `$` can be written as `\$`. Formulas are rendered before the [preprocessors](#preprocessors) run, so they aren't touched by them.
 //! * `html_lang`: Changes the starting `<html>` tag (e.g. *"es"* `<html lang="es">`).
-//! * `syntax_highlighting`: Enables syntax highlighting using [`highlight.js`](https://highlightjs.org/).
+//! * `syntax_highlighting`: Highlights code blocks while building, configured in [`[highlighting]`](#config.highlighting).
+//! * `search`: Generates a search index (`search-index.json`) with the title, url, headings and words of every page, and adds a search box to the sidebar. It works without a server, so it can be used with any static hosting. *(default: `true`)*
+//! * `theme` *(optional)*: Name of a theme installed in `<CARGO HOME>/cuteness-config/themes`, see [*Templates and themes*](#templates).
+//! * `template_version` *(optional)*: Tag, branch or commit of the templates installed by [`cuteness update`](#subcommands.update) (default: `main`).
+//!
+//! ### `[highlighting]` <a name="config.highlighting"></a>
+//!
+//! With `syntax_highlighting = true`, fenced code blocks are highlighted at build time with [`syntect`](https://github.com/trishume/syntect), so pages don't need any JavaScript. Tokens become `<span>`s with `hl-` prefixed classes, and the theme's colors are written to `styles/highlight.css`.
+//!
+//! * `theme`: One of the bundled themes: `InspiredGitHub` (default), `Solarized (dark)`, `Solarized (light)`, `base16-eighties.dark`, `base16-mocha.dark`, `base16-ocean.dark` or `base16-ocean.light`.
+//! * `line_numbers`: Numbers the lines of every code block.
+//! * `languages`: Only highlights code blocks in these languages (e.g. `["rust", "python"]`), the rest are still split in lines but not colored. Every language is highlighted if it's empty.
+//!
+//! Lines can be highlighted by listing them (or ranges of them) after the language:
+//!
+//! ````md
+//! ```rust {1,3-4}
+//! fn main() {
+//!     let name = "cuteness";
+//!     println!("Hello, {name}!");
+//!     println!("Bye!");
+//! }
+//! ```
+//! ````
+//!
+//! ### `[preprocessors]` <a name="config.preprocessors"></a>
+//!
+//! Enables, disables and configures the [preprocessors](#preprocessors). They run in the order they're listed in; the ones that aren't listed are enabled with their default options and run after them. Each one is either `true`, `false` or a table with its options (and `enabled = false` to disable it).
+//!
+//! * `curly_quotes`: `quotes` is `"auto"` (default) to use the quotes of the language in `html_lang` (e.g. “” for `en`, «» for `fr`, „“ for `de`, 「」 for `ja`), or the opening and closing quotes, optionally followed by the single ones (e.g. `"«»"` or `"„“‚‘"`).
+//! * `emojis`: No options.
+//! * Any entry with a `command` is an [external preprocessor](#preprocessors.external), its other options are passed to it.
+//!
+//! ### `[validation]` <a name="config.validation"></a>
+//!
+//! After building, `SUMMARY.toml` is checked against the pages in `src`. Each check can be set to `"error"` (the build fails), `"warning"` (default) or `"ignore"`.
+//!
+//! * `dangling_entries`: An entry in `SUMMARY.toml` links to a page that doesn't exist.
+//! * `orphan_pages`: A page isn't linked from `SUMMARY.toml`.
 //!
 //! ### `[config]` <a name="config.config"></a>
 //!
@@ -64,6 +149,7 @@
 //! * `title`: The current page's title.
 //! * `pageconf` *(optional)*: User-provided page configuration (Key-value pairs).
 //! * `additional_css` *(optional)*: Additional CSS files needed to properly render the page. **(`index.css` is imported by default)**
+//! * `preprocessors` *(optional)*: `false` disables every [preprocessor](#preprocessors) in this page, a map disables some of them (e.g. `preprocessors: { emojis: false }`).
 //!
 //! ### Example <a name="frontmatter.example"></a>
 //!
@@ -97,6 +183,8 @@
 //!
 //! `{{page.*}}` is the interface that you can access in order to use page configuration. For example, you can use `{{page.title}}` to access the page's title.
 //!
+//! Every heading gets an anchor generated from its text (`## Getting started` becomes `<h2 id="getting-started">`, repeated headings get `-1`, `-2`...), with its Handlebars expressions rendered (`# {{page.title}}` uses the page's title), unless you give it one yourself with `## Getting started {#start}` (generated anchors never take an anchor given this way, and giving the same one twice prints a warning). The page's headings are available in `{{page.toc}}`, each one with its `level`, `id` and `title`, so the page template can show an *"On this page"* panel.
+//!
 //! #### Example <a name="templating.page.example"></a>
 //!
 //! ```md
@@ -212,6 +300,49 @@
 //!
 //! When creating a new file, you'll have to start the file writing a [front-matter](#frontmatter) and then the contents of your file. As explained in [*Templating*](#templating), you can use [Handlebars templates](https://handlebarsjs.com/).
 //!
+//! ## `SUMMARY.toml` <a name="summary"></a>
+//!
+//! `SUMMARY.toml` is the table of contents displayed in the sidebar. Each `[[map]]` entry is one of:
+//!
+//! * A chapter, with a `title` and an `url`. Chapters can have sections, nested as much as you want with `[[map.children]]`.
+//! * A part header, with `part = "Part title"`.
+//! * A separator, with `separator = true`.
+//!
+//! Adding `numbered = true` at the top of the file numbers every chapter and section (*1*, *1.2*, *1.2.3*...). A chapter with `numbered = false` (e.g. a preface) is left without a number.
+//!
+//! ```toml
+//! # SUMMARY.toml
+//! numbered = true
+//!
+//! [[map]]
+//! title = "Preface"
+//! url = "preface"
+//! numbered = false
+//!
+//! [[map]]
+//! part = "User guide"
+//!
+//! [[map]]
+//! title = "Installation"
+//! url = "installation"
+//!
+//!     [[map.children]]
+//!     title = "Installing on Linux"
+//!     url = "installation-linux"
+//!
+//! [[map]]
+//! separator = true
+//!
+//! [[map]]
+//! title = "Appendix"
+//! url = "appendix"
+//! numbered = false
+//! ```
+//!
+//! The whole structure is available to the page template as `sidebar` (e.g. `{{#each sidebar.map}}`); each entry has its `title`, `url`, `children`, `part`, `separator` and computed `number`. While rendering a page, its entry has `is_current` set, and the chapters containing it have `is_ancestor` set, so the sidebar can highlight it and expand its section (the default template only shows the sections of these chapters). The current page's url is also available as `current_url`. Custom templates should start links to files in the site with `{{root}}` (e.g. `{{root}}styles/index.css`): it's the path from the current page to the root of the site (e.g. `../` for `src/guide/intro.md`).
+//!
+//! The page template also gets `prev` and `next` (each with a `title` and an `url`): the chapters before and after the current page when reading the summary from top to bottom. They're empty on the first and last pages, and for pages that aren't in the summary.
+//!
 //! # Subcommands <a name="subcommands"></a>
 //! ## `init` <a name="subcommands.init"></a>
 //!
@@ -222,15 +353,31 @@
 //! * `SUMMARY.toml` in the root directory with [default contents](https://github.com/blyxyas/cuteness/blob/main/SUMMARY.default.toml).
 //! * `cuteconfig.toml` in the root directory with [default contents](https://github.com/blyxyas/cuteness/blob/main/cuteconfig.default.toml).
 //!
-//! You can start by writing on `introduction.md`, then executing `cuteness build`, executing `cargo run --manifest-path <output directory, default: www>/routing/Cargo.toml` and going to *http://localhost:8080/introduction*
+//! You can start by writing on `introduction.md`, then executing `cuteness build`, executing `cargo run --manifest-path <output directory, default: www>/routing/Cargo.toml` and going to *http://localhost:8080/introduction*. While writing, [`cuteness serve`](#subcommands.serve) is a quicker way to preview your pages.
 //!
 //! ## `build` <a name="subcommands.build"></a>
 //!
 //! `cuteness build` is used to build the project, it will create an output directory containing the built version (using all your configurations) of your `src` directory. If there are `.sass` files in the directory `src/styles` it will also compile those.
 //!
+//! Builds are incremental: the hashes of every input are stored in `<output directory>/.cuteness-cache` (except with `--target static`), and a page is only rendered again if its source file changed, or if `cuteconfig.toml`, `SUMMARY.toml` or the templates did (or always, with [external preprocessors](#preprocessors.external)). Deleting that file (or using [`cuteness clean`](#subcommands.clean)) forces a full build.
+//!
+//! With `--watch`, it will keep running after the build and rebuild only what's needed when something changes: editing a Markdown file re-renders that page, editing `SUMMARY.toml` or `cuteconfig.toml` re-renders every page, and editing `src/styles` recompiles the styles.
+//!
+//! With `--target static`, it only generates the pages for static hosting (GitHub Pages, Netlify, a bucket...), without the `routing` web-server: the pages are written at the root of the output directory, with `styles/` and the search index next to them, so the directory can be uploaded as-is. Pages are written as `introduction/index.html`, served at `/introduction/` (`--urls pretty`, default) or as `introduction.html` (`--urls html`), except `404.html`, which static hosts serve for missing pages. Every link in the sidebar, the pages and the templates points to the right file: links in the pages are relative to the page, like with the server, so `../introduction` in `src/guide/intro.md` links to `src/introduction.md` (links that go above the root of the site are left as they are, with a warning). Pages with [parameters](#routing) need the web-server, so they're left out. Static builds aren't incremental, as the output directory shouldn't contain anything but the site. Instead, they list the files they write in `<output directory>/.cuteness-output`, so the next build can remove the ones that aren't generated anymore (e.g. deleted pages). Building for one target removes what a build for the other one left in the output directory, but only the files it knows cuteness wrote: anything else in it is left as it is.
+//!
+//! ## `serve` <a name="subcommands.serve"></a>
+//!
+//! `cuteness serve` builds the project and serves the output directory's `static` files with a small built-in web-server, without compiling the generated Rocket project. Pages are served the same way the generated router does (*http://localhost:8080/introduction* serves `introduction.html`).
+//!
+//! While it's running, changes to `src`, `SUMMARY.toml` and `cuteconfig.toml` trigger a rebuild, and every open browser tab reloads automatically. It accepts the `--port`, `--outdir` and `--sassbin` options of `build`: it always builds the server target (`--target server`) and always watches for changes.
+//!
 //! ## `setup` <a name="subcommands.setup"></a>
 //!
-//! `cuteness setup` is a one-time command, it's used to get all necessary template files from the web. **It requires internet connection**. You can think of it as an enhanced `git clone` that only clones necessary files.
+//! `cuteness setup` is a one-time command, it's used to get the latest template files from the web. **It requires internet connection**. You can think of it as an enhanced `git clone` that only clones necessary files.
+//!
+//! On machines without network access, `cuteness setup --from <path>` installs the templates from a local directory or a tarball instead (e.g. a downloaded release of cuteness, or its `templates` directory). If any step fails, the half-created configuration directory is removed so the command can be run again.
+//!
+//! It's optional: the default templates are built into the binary, and they're used for every template that isn't in the configuration directory, so `cuteness build` also works offline.
 //!
 //! **NOTE**: This command will create a directory called `cuteness-config` at your Cargo home (usually `~/.cargo/` on Unix systems) and store there all your internal configurations. (Do not edit manually.)
 //!
@@ -238,6 +385,32 @@
 //!
 //! `cuteness update` will update the internal templates and styles to the latest version; you can think of it as an enhanced `git pull`.
 //!
+//! Inside a project, it checks out exactly the `template_version` set in [`[misc]`](#config.misc) instead, and writes the commit it resolved to in `cuteness.lock`:
+//!
+//! ```toml
+//! # cuteness.lock
+//! [templates]
+//! version = "v0.8.1"
+//! commit = "4f1c9a0e..."
+//! ```
+//!
+//! `cuteness update --from <path>` replaces the templates with the ones in a local directory or tarball, like `cuteness setup --from`.
+//!
+//! Commit `cuteness.lock` with the rest of the project: `cuteness update --locked` checks out the commit in the lock file, so everyone builds with the same templates. `cuteness build` warns if the installed templates aren't the locked ones.
+//!
+//! ## `theme` <a name="subcommands.theme"></a>
+//!
+//! `cuteness theme` manages the themes installed in `<CARGO HOME>/cuteness-config/themes` (see [*Templates and themes*](#templates)):
+//!
+//! * `cuteness theme list` lists the installed themes.
+//! * `cuteness theme add <path or git url>` installs a theme from a local directory or clones it from a git repository.
+//! * `cuteness theme use <name>` selects an installed theme for the current project, setting `theme` in the `[misc]` section of `cuteconfig.toml`.
+//! * `cuteness theme remove <name>` uninstalls a theme.
+//!
+//! ## `doctor` <a name="subcommands.doctor"></a>
+//!
+//! `cuteness doctor` checks everything a build needs and prints what's wrong and how to fix it: the configuration directory and its templates, `git` and the Sass compiler (`--sassbin`), whether `cuteconfig.toml`, `SUMMARY.toml` and the templates parse, whether the installed templates match `cuteness.lock`, and whether the output directory (`--outdir`) is writable. It exits with an error if any check failed.
+//!
 //! ## `clean` <a name="subcommands.clean"></a>
 //!
 //! `cuteness clean` will delete the output directory (default: `www`). It's not usually necessary.
@@ -266,18 +439,103 @@
 //!
 //! Almost the same, just locate your `.css` files at `src/styles` and they will not get compiled, but only copied to the output directory.
 //!
+//! # Templates and themes <a name="templates"></a>
+//!
+//! Every template and built-in style is looked up in these places, in order, so you can override a single file and keep the rest:
+//!
+//! 1. The project's `templates` directory (e.g. `templates/page.html.hbs` or `templates/styles/index.css`).
+//! 2. The theme selected with `theme` in [`[misc]`](#config.misc), installed at `<CARGO HOME>/cuteness-config/themes/<name>`.
+//! 3. `<CARGO HOME>/cuteness-config/templates`, installed by [`cuteness setup`](#subcommands.setup).
+//! 4. The default templates built into the binary.
+//!
+//! The files that can be overridden are `page.html.hbs`, `routing.go.hbs`, everything in `routing/` (Rocket), `routing-axum/`, `proxy/` and `styles/`. Handlebars partials can be added in `partials/` (`partials/footer.hbs` is used as `{{> footer}}` in `page.html.hbs`), and they're looked up the same way.
+//!
+//! ```text
+//! .
+//! ├── cuteconfig.toml
+//! ├── src
+//! ├── SUMMARY.toml
+//! └── templates
+//!     ├── page.html.hbs
+//!     ├── partials
+//!     │   └── footer.hbs
+//!     └── styles
+//!         └── index.css
+//! ```
+//!
+//! A theme is a directory with the same layout as `templates` and a `theme.toml` manifest. Themes are installed and selected with [`cuteness theme`](#subcommands.theme), and a theme that requires another version of cuteness isn't used.
+//!
+//! ```toml
+//! # theme.toml
+//! name = "my-theme" # Letters, numbers, `-` and `_`
+//! version = "1.0.0"
+//! cuteness = ">=0.8, <0.9" # Versions of cuteness the theme works with
+//! ```
+//!
+//! Changes to the `templates` directory are picked up by `cuteness serve` and `cuteness build --watch`.
+//!
 //! # Routing <a name="routing"></a>
 //!
 //! When using `cuteness build`, an output directory containing some static files and a simple web-server will be generated which you can access by going to *http://localhost:8080/*
 //!
+//! The web-server is a Rocket project by default, an Axum project with `backend = "axum"` or a Go program with `backend = "go"` in [`[routing]`](#config.routing).
+//!
+//! Every page is served at its path in `src` without `.md`, answering the `method` in its front-matter (`GET` by default). A page can take parameters from its path: `src/user/<id>.md` is served at `/user/<id>` for any `id`, and its front-matter declares their types (`string`, `int`, `long`, `float`, `longfloat` or `array<T>`). With the Axum backend, requests with a parameter of the wrong type are rejected, and `{{id}}` in the page is replaced with the parameter's value.
+//!
+//! ```md
+//! # src/user/<id>.md
+//! ---
+//! title: "User"
+//! params:
+//!   - type: int
+//!     name: id
+//! ---
+//!
+//! This is user number {{id}}.
+//! ```
+//!
+//! If you don't need the web-server, [`cuteness build --target static`](#subcommands.build) generates only the pages.
+//!
 //! As the project is still in development, efforts about using actual servers available on the internet are still very far from being started.
 //!
 //! # Preprocessors <a name="preprocessors"></a>
 //!
-//! The files content are preprocessed before being written, these preprocessors are used to change \"straight quotes\" to “curly quotes”, or to change emojicodes "`:cat:`" to actual emojis 🐱. These preprocessors are applied automatically and should not cause any problems.
+//! The files content are preprocessed before being written, these preprocessors are used to change \"straight quotes\" to “curly quotes” (`curly_quotes`), or to change emojicodes "`:cat:`" to actual emojis 🐱 (`emojis`). They're enabled by default and can be disabled or configured in [`[preprocessors]`](#config.preprocessors), or disabled for a single page in its [front-matter](#frontmatter). They should not cause any problems: they only change the text of the page, after it's parsed as Markdown, so code spans, code blocks, HTML, autolinks, URLs and Handlebars expressions (`{{...}}`) are left as they are. Emojicodes are only replaced if they're a known emoji, so times like `10:30:00` aren't touched either.
+//!
+//! Every preprocessor implements the `Preprocessor` trait of the `cuteness` crate.
+//!
+//! ## External preprocessors <a name="preprocessors.external"></a>
+//!
+//! Like [mdBook's](https://rust-lang.github.io/mdBook/for_developers/preprocessors.html), any command can be a preprocessor, so project-specific tools don't need to be part of cuteness. They're declared in [`[preprocessors]`](#config.preprocessors) with a `command`, and run in the order they're listed, on the Markdown of every page, before it's parsed (and before any built-in preprocessor). A page can opt out of them in its [front-matter](#frontmatter) like of any other preprocessor.
+//!
+//! ```toml
+//! # cuteconfig.toml
+//! [preprocessors]
+//! glossary = { command = "python3 tools/glossary.py", terms = "glossary.toml" }
+//! ```
+//!
+//! The command is split into arguments like a shell would, so arguments with spaces can be quoted (`command = "python3 'my tools/glossary.py'"`), but it isn't run by a shell. It's run from the project's root and gets the page as JSON on its standard input:
+//!
+//! * `page`: The page's front-matter.
+//! * `content`: The page's Markdown, without the front-matter.
+//! * `path`: Path of the page, e.g. `src/guide/intro.md`.
+//! * `outer`: The whole `cuteconfig.toml`.
+//! * `options`: The preprocessor's options in `[preprocessors]`, except `command` (e.g. `{"terms": "glossary.toml"}`).
+//!
+//! And it writes `{"content": "..."}`, the new Markdown, to its standard output. If it exits with an error, the build fails; anything it writes to its standard error is shown. For example, a preprocessor that adds a footer to every page:
+//!
+//! ```python
+//! # tools/footer.py
+//! import json, sys
+//!
+//! page = json.load(sys.stdin)
+//! json.dump({"content": page["content"] + "\n\n---\n\n*" + page["page"]["title"] + "*"}, sys.stdout)
+//! ```
+//!
+//! As a preprocessor can depend on any file (its own code, its data...), builds with external preprocessors aren't [incremental](#subcommands.build): every page is rendered again.
 //!
 #![doc = ::document_features::document_features!()]
-//! [^1]: The tool specifically uses [KaTeX](https://katex.org/), specialized on equations.
+//! [^1]: The tool specifically uses [`latex2mathml`](https://github.com/osanshouo/latex2mathml), which supports the most common LaTeX math commands.
 //!
 //! [^3]: `Handlebars-rs` uses the [Handlebars templating language](https://handlebarsjs.com/)
 //!
@@ -287,7 +545,6 @@
 
 use core::panic;
 use std::{
-    borrow::Cow,
     fs::{create_dir, remove_dir_all},
     path::{Path, PathBuf},
     process::Command,
@@ -298,9 +555,20 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
+pub mod cache;
+pub mod external;
+pub mod highlight;
+pub mod lock;
+pub mod markdown;
+pub mod math;
+pub mod search;
+pub mod serve;
+pub mod summary;
+pub mod templates;
+pub mod theme;
+pub mod watch;
+
 lazy_static! {
-    static ref REQUOTE: Regex = Regex::new("\"(.*?)\"").unwrap();
-    static ref REEMOJI: Regex = Regex::new(":(.*?):").unwrap();
     pub static ref CONFIG_PATH: PathBuf = home::cargo_home()
         .expect("Couldn't get Cargo home")
         .join("cuteness-config");
@@ -310,21 +578,248 @@ lazy_static! {
 
 const REPO_URL: &str = "https://github.com/blyxyas/cuteness.git";
 
-/// Replace straight quotes (") with curly quotes, U+201C (“) and U+201D (”)
-#[inline(always)]
-pub fn curly_quotes(content: &str) -> Cow<'_, str> {
-    REQUOTE.replace_all(content, "“$1”")
+/// A transform applied to the prose of every page (everything but code, HTML, URLs and Handlebars expressions), enabled and configured in `[preprocessors]`.
+pub trait Preprocessor: Send + Sync {
+    /// Name of the preprocessor in `[preprocessors]` and in a page's front-matter
+    fn name(&self) -> &str;
+    /// Transforms a piece of text. `previous` is the character before it in its paragraph, if any.
+    fn run(&self, text: &str, previous: Option<char>) -> String;
+}
+
+/// Built-in preprocessors, in the order they run unless `[preprocessors]` lists them in another one.
+pub const BUILTIN_PREPROCESSORS: [&str; 2] = ["curly_quotes", "emojis"];
+
+/// The preprocessors enabled in `[preprocessors]`, each kind in the order they're listed.
+#[derive(Default)]
+pub struct Preprocessors {
+    /// Commands run on the Markdown of every page before it's parsed
+    pub external: Vec<external::External>,
+    /// Run on the text of every page while it's converted to HTML
+    pub builtin: Vec<Box<dyn Preprocessor>>,
+}
+
+/// Builds the preprocessors enabled in `[preprocessors]`. An entry with a `command` is an [external](external) preprocessor, any other one must be a built-in one. Built-in preprocessors that aren't listed are enabled with their default options and run after the listed ones. `html_lang` chooses the quotes of `curly_quotes` unless they're configured.
+pub fn preprocessors(config: &toml::Table, html_lang: &str) -> anyhow::Result<Preprocessors> {
+    use anyhow::Context;
+
+    let mut preprocessors = Preprocessors::default();
+    for (name, entry) in config {
+        let options = match entry {
+            toml::Value::Boolean(false) => continue,
+            toml::Value::Boolean(true) => toml::Table::new(),
+            toml::Value::Table(options) => {
+                if options.get("enabled") == Some(&toml::Value::Boolean(false)) {
+                    continue;
+                }
+                options.clone()
+            }
+            _ => anyhow::bail!(
+                "`[preprocessors] {name}` must be `true`, `false` or a table of options"
+            ),
+        };
+
+        if options.contains_key("command") {
+            preprocessors.external.push(
+                external::External::new(name, &options)
+                    .with_context(|| format!("Invalid `[preprocessors] {name}`"))?,
+            );
+        } else {
+            preprocessors.builtin.push(
+                builtin(name, &options, html_lang)
+                    .with_context(|| format!("Invalid `[preprocessors] {name}`"))?,
+            );
+        }
+    }
+
+    for name in BUILTIN_PREPROCESSORS {
+        if !config.contains_key(name) {
+            preprocessors
+                .builtin
+                .push(builtin(name, &toml::Table::new(), html_lang)?);
+        }
+    }
+    Ok(preprocessors)
+}
+
+fn builtin(
+    name: &str,
+    options: &toml::Table,
+    html_lang: &str,
+) -> anyhow::Result<Box<dyn Preprocessor>> {
+    let known: &[&str] = match name {
+        "curly_quotes" => &["enabled", "quotes"],
+        "emojis" => &["enabled"],
+        _ => anyhow::bail!(
+            "Unknown preprocessor `{name}` (built-in preprocessors: `{}`, external ones need a `command`)",
+            BUILTIN_PREPROCESSORS.join("`, `")
+        ),
+    };
+    if let Some(option) = options
+        .keys()
+        .find(|option| !known.contains(&option.as_str()))
+    {
+        anyhow::bail!("Unknown option `{option}`");
+    }
+
+    Ok(match name {
+        "curly_quotes" => Box::new(CurlyQuotes {
+            quotes: match options.get("quotes") {
+                None => Quotes::for_lang(html_lang),
+                Some(toml::Value::String(style)) if style == "auto" => Quotes::for_lang(html_lang),
+                Some(toml::Value::String(style)) => Quotes::parse(style)?,
+                Some(_) => anyhow::bail!("`quotes` must be a string, e.g. \"«»\""),
+            },
+        }),
+        _ => Box::new(Emojis),
+    })
+}
+
+/// Opening and closing quotes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quotes {
+    pub double: (char, char),
+    pub single: (char, char),
 }
 
-/// Replaces all emojicodes (:cat:) to real emojis
-#[inline(always)]
-pub fn emojis(content: &str) -> String {
-    let mut result = <&str>::clone(&content).to_string();
-    for cap in REEMOJI.find_iter(content) {
-        if let Some(emoji) = get_by_shortcode(&content[cap.start() + 1..cap.end() - 1]) {
-            result = content.replace(cap.as_str(), emoji.as_str());
+impl Quotes {
+    /// The quotes used in the language `lang` (as in `html_lang`, e.g. `fr` or `de-AT`), English ones if it's not known.
+    pub fn for_lang(lang: &str) -> Self {
+        let lang = lang.to_lowercase();
+        let (double, single) = match lang.split(['-', '_']).next().unwrap_or_default() {
+            "de" | "cs" | "sk" | "sl" | "lt" | "is" | "bg" => (('„', '“'), ('‚', '‘')),
+            "pl" | "hu" | "ro" | "hr" | "nl" => (('„', '”'), ('‚', '’')),
+            "fr" => (('«', '»'), ('‹', '›')),
+            "es" | "it" | "pt" | "ca" | "el" | "ru" | "uk" | "be" | "no" | "nb" | "nn" => {
+                (('«', '»'), ('“', '”'))
+            }
+            "da" => (('»', '«'), ('›', '‹')),
+            "sv" | "fi" => (('”', '”'), ('’', '’')),
+            "ja" => (('「', '」'), ('『', '』')),
+            // Traditional Chinese, simplified Chinese uses the English ones
+            "zh" if ["tw", "hk", "mo", "hant"]
+                .iter()
+                .any(|region| lang.split(['-', '_']).any(|subtag| subtag == *region)) =>
+            {
+                (('「', '」'), ('『', '』'))
+            }
+            _ => (('“', '”'), ('‘', '’')),
         };
+        Quotes { double, single }
+    }
+
+    /// Parses the quotes in `style`: the opening and closing double quotes, optionally followed by the single ones (e.g. `«»` or `„“‚‘`). Single quotes are English ones if they're not given.
+    pub fn parse(style: &str) -> anyhow::Result<Self> {
+        let chars: Vec<char> = style.chars().collect();
+        match chars[..] {
+            [open, close] => Ok(Quotes {
+                double: (open, close),
+                single: ('‘', '’'),
+            }),
+            [open, close, single_open, single_close] => Ok(Quotes {
+                double: (open, close),
+                single: (single_open, single_close),
+            }),
+            _ => anyhow::bail!("Invalid quotes `{style}`, they should be 2 or 4 characters like \"«»\" or \"„“‚‘\""),
+        }
+    }
+}
+
+impl Default for Quotes {
+    fn default() -> Self {
+        Quotes::for_lang("en")
+    }
+}
+
+/// The `curly_quotes` preprocessor
+pub struct CurlyQuotes {
+    pub quotes: Quotes,
+}
+
+impl Preprocessor for CurlyQuotes {
+    fn name(&self) -> &str {
+        "curly_quotes"
+    }
+
+    fn run(&self, text: &str, previous: Option<char>) -> String {
+        curly_quotes(text, previous, self.quotes)
+    }
+}
+
+/// The `emojis` preprocessor
+pub struct Emojis;
+
+impl Preprocessor for Emojis {
+    fn name(&self) -> &str {
+        "emojis"
+    }
+
+    fn run(&self, text: &str, _previous: Option<char>) -> String {
+        emojis(text)
     }
+}
+
+/// Replace straight quotes (" and ') with curly `quotes`. A quote is an opening one if it's at the start of the paragraph or after a space or a bracket, `previous` is the character before `text` (`None` at the start of a paragraph). A `'` between two letters is always an apostrophe (’).
+pub fn curly_quotes(text: &str, previous: Option<char>, quotes: Quotes) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut previous = previous;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '"' || c == '\'' {
+            let opening = previous.is_none_or(|p| {
+                p.is_whitespace()
+                    || "([{-–—".contains(p)
+                    || p == quotes.double.0
+                    || p == quotes.single.0
+            });
+            let apostrophe = c == '\''
+                && previous.is_some_and(char::is_alphanumeric)
+                && chars.peek().is_some_and(|next| next.is_alphanumeric());
+            result.push(match (c, opening) {
+                _ if apostrophe => '’',
+                ('"', true) => quotes.double.0,
+                ('"', false) => quotes.double.1,
+                (_, true) => quotes.single.0,
+                (_, false) => quotes.single.1,
+            });
+        } else {
+            result.push(c);
+        }
+        previous = Some(c);
+    }
+    result
+}
+
+/// Replaces all emojicodes (:cat:) to real emojis, leaving anything else between colons (e.g. `10:30:00`) as it is.
+pub fn emojis(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let emoji = after
+            .split_once(':')
+            .map(|(code, _)| code)
+            .filter(|code| {
+                !code.is_empty()
+                    && code
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "_+-".contains(c))
+            })
+            .and_then(|code| Some((code, get_by_shortcode(code)?)));
+
+        match emoji {
+            Some((code, emoji)) => {
+                result.push_str(emoji.as_str());
+                rest = &after[code.len() + 1..];
+            }
+            // The closing colon may be the start of the next emojicode
+            None => {
+                result.push(':');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
     result
 }
 
@@ -339,85 +834,209 @@ pub fn params_in_path(path: &Path) -> Vec<String> {
     result
 }
 
-#[inline]
-pub fn setup() {
-    if CONFIG_PATH.exists() {
-        return;
+/// Creates `CONFIG_PATH` with the templates from upstream `main`, or from `from` (a directory or a tarball) on machines without network access. If anything fails, `CONFIG_PATH` is removed so `setup` can be run again.
+pub fn setup(from: Option<&Path>) -> anyhow::Result<()> {
+    use anyhow::Context;
+
+    // The configuration directory may already exist without templates, e.g. if a theme was added first
+    if CONFIG_PATH.join("templates").exists() {
+        println!(
+            "Cuteness is already configured at `{}`",
+            CONFIG_PATH.display()
+        );
+        return Ok(());
     }
 
-    create_dir(CONFIG_PATH.as_path())
-        .unwrap_or_else(|e| panic!("Couldn't create directory `{}`: {e}", CONFIG_PATH.display()));
+    let created = !CONFIG_PATH.exists();
+    std::fs::create_dir_all(CONFIG_PATH.as_path())
+        .with_context(|| format!("Couldn't create directory `{}`", CONFIG_PATH.display()))?;
 
-    // Initialize git repo
+    let result = match from {
+        Some(source) => install_local(source),
+        None => clone_templates(),
+    };
+    if let Err(e) = result {
+        // Only remove what we added, the themes have to stay
+        if created {
+            let _ = remove_dir_all(CONFIG_PATH.as_path());
+        } else {
+            let _ = remove_dir_all(CONFIG_PATH.join("templates"));
+            let _ = remove_dir_all(CONFIG_PATH.join(".git"));
+        }
+        return Err(e.context("Couldn't set up cuteness"));
+    }
 
-    Command::new("git")
-        .current_dir(CONFIG_PATH.as_path())
-        .args(["init"])
-        .status()
-        .expect("Couldn't initialize repository");
+    println!("Cuteness was successfully configured!");
+    Ok(())
+}
 
-    // Add this repo as remote
+/// Sparse-clones the `templates` directory of upstream `main` into `CONFIG_PATH`.
+fn clone_templates() -> anyhow::Result<()> {
+    use anyhow::Context;
 
-    Command::new("git")
-        .current_dir(CONFIG_PATH.as_path())
-        .args(["remote", "add", "origin", REPO_URL])
-        .status()
-        .expect("Couldn't add remote");
+    git(&["init"])?;
+    git(&["remote", "add", "origin", REPO_URL])?;
+    git(&["config", "core.sparseCheckout", "true"])?;
 
-    // Enable sparse checkout
+    let sparse_checkout = CONFIG_PATH
+        .join(".git")
+        .join("info")
+        .join("sparse-checkout");
+    std::fs::write(&sparse_checkout, "templates/*\n")
+        .with_context(|| format!("Couldn't write to `{}`", sparse_checkout.display()))?;
 
-    Command::new("git")
-        .current_dir(CONFIG_PATH.as_path())
-        .args(["config", "core.sparseCheckout", "true"])
-        .status()
-        .expect("Couldn't enable `core.sparseCheckout`");
+    git(&["pull", "--depth=1", "origin", "main"])?;
+    Ok(())
+}
 
-    // Add templates to sparse-checkout
+/// Replaces `CONFIG_PATH/templates` with the templates in `source`: a directory or a tarball (e.g. a downloaded release) containing the `templates` directory, or the `templates` directory itself.
+///
+/// These templates aren't a git checkout, so `.git` is removed from `CONFIG_PATH` and a later `cuteness update` without `--from` needs `cuteness uninstall` and `cuteness setup` first.
+pub fn install_local(source: &Path) -> anyhow::Result<()> {
+    use anyhow::Context;
 
-    std::fs::write(
-        CONFIG_PATH
-            .join(".git")
-            .join("info")
-            .join("sparse-checkout"),
-        "templates/*",
-    )
-    .unwrap_or_else(|e| {
-        panic!(
-            "Couldn't write to {}: {e}",
-            CONFIG_PATH
-                .join(".git")
-                .join("info")
-                .join("sparse-checkout")
-                .display()
-        )
-    });
-
-    Command::new("echo")
-        .current_dir(CONFIG_PATH.as_path())
-        .args(["templates/*", ">>", ".git/info/sparse-checkout"])
-        .status()
-        .expect("Couldn't add templates to `.git/info/sparse-checkout`");
+    let staging = CONFIG_PATH.join(".staging");
+    if staging.exists() {
+        remove_dir_all(&staging)
+            .with_context(|| format!("Couldn't remove directory `{}`", staging.display()))?;
+    }
 
-    // Pull
+    let result = (|| {
+        let root = if source.is_dir() {
+            source.to_path_buf()
+        } else if source.is_file() {
+            let extracted = staging.join("archive");
+            std::fs::create_dir_all(&extracted)
+                .with_context(|| format!("Couldn't create directory `{}`", extracted.display()))?;
+            let status = Command::new("tar")
+                .arg("-xf")
+                .arg(source)
+                .arg("-C")
+                .arg(&extracted)
+                .status()
+                .context("Couldn't run `tar`")?;
+            if !status.success() {
+                anyhow::bail!(
+                    "Couldn't extract `{}` (`tar` exited with {status})",
+                    source.display()
+                );
+            }
+            extracted
+        } else {
+            anyhow::bail!("Couldn't find `{}`", source.display());
+        };
 
-    Command::new("git")
-        .current_dir(CONFIG_PATH.as_path())
-        .args(["pull", "--depth=1", "origin", "main"])
-        .status()
-        .expect("Couldn't pull");
+        let templates = find_templates(&root).with_context(|| {
+            format!(
+                "Couldn't find the templates (a directory with `page.html.hbs`) in `{}`",
+                source.display()
+            )
+        })?;
+        copy_dir(&templates, &staging.join("templates"))?;
 
-    println!("Cuteness was successfully configured!");
+        let destination = CONFIG_PATH.join("templates");
+        if destination.exists() {
+            remove_dir_all(&destination).with_context(|| {
+                format!("Couldn't remove directory `{}`", destination.display())
+            })?;
+        }
+        std::fs::rename(staging.join("templates"), &destination).with_context(|| {
+            format!("Couldn't move the templates to `{}`", destination.display())
+        })?;
+
+        let git_dir = CONFIG_PATH.join(".git");
+        if git_dir.exists() {
+            remove_dir_all(&git_dir)
+                .with_context(|| format!("Couldn't remove directory `{}`", git_dir.display()))?;
+        }
+        Ok(())
+    })();
+
+    if staging.exists() {
+        let _ = remove_dir_all(&staging);
+    }
+    result
 }
 
-#[inline]
-pub fn check_for_updates() {
-    Command::new("git")
-        .current_dir(CONFIG_PATH.as_path())
-        .args(["pull", "--depth=1", "origin", "main", "--rebase"])
-        .status()
-        .expect("Couldn't pull");
+/// The templates directory in `root`: `root/templates`, `root` itself, or `templates` inside one of its directories (archives usually have a single top-level directory).
+fn find_templates(root: &Path) -> Option<PathBuf> {
+    let is_templates = |dir: &Path| dir.join("page.html.hbs").is_file();
 
-    println!("Repository updated!");
+    let mut candidates = vec![root.join("templates"), root.to_path_buf()];
+    if let Ok(entries) = std::fs::read_dir(root) {
+        let mut children: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path().join("templates"))
+            .collect();
+        children.sort();
+        candidates.extend(children);
+    }
+    candidates.into_iter().find(|dir| is_templates(dir))
+}
+
+/// Copies the directory `from` to `to`, skipping `.git`.
+pub(crate) fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+    use anyhow::Context;
+
+    for entry in walkdir::WalkDir::new(from)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+    {
+        let entry = entry.with_context(|| format!("Couldn't read `{}`", from.display()))?;
+        let target = to.join(entry.path().strip_prefix(from).unwrap());
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target)
+                .with_context(|| format!("Couldn't create directory `{}`", target.display()))?;
+        } else {
+            std::fs::copy(entry.path(), &target)
+                .with_context(|| format!("Couldn't copy `{}`", entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Fetches `version` of the templates (a tag, branch or commit) and checks it out, returning the commit it resolved to.
+pub fn check_for_updates(version: &str) -> anyhow::Result<String> {
+    if !CONFIG_PATH.join(".git").exists() {
+        anyhow::bail!(
+            "The templates in `{}` weren't installed from the repository; use `cuteness update --from <directory or tarball>`, or `cuteness uninstall` and `cuteness setup`",
+            CONFIG_PATH.display()
+        );
+    }
+
+    git(&["fetch", "--depth=1", "origin", version])?;
+    git(&["checkout", "--force", "--detach", "FETCH_HEAD"])?;
+    let commit = templates_commit()?.unwrap_or_default();
+
+    println!("Templates updated to `{version}` ({commit})!");
+    Ok(commit)
+}
+
+/// The commit the templates in `CONFIG_PATH` are checked out at, if they were installed with `cuteness setup`.
+pub fn templates_commit() -> anyhow::Result<Option<String>> {
+    if !CONFIG_PATH.join(".git").exists() {
+        return Ok(None);
+    }
+    git(&["rev-parse", "HEAD"]).map(Some)
+}
+
+/// Runs `git` in `CONFIG_PATH`, returning its output.
+fn git(args: &[&str]) -> anyhow::Result<String> {
+    use anyhow::Context;
+
+    let output = Command::new("git")
+        .current_dir(CONFIG_PATH.as_path())
+        .args(args)
+        .output()
+        .context("Couldn't run `git`")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
 
 #[inline]
@@ -503,7 +1122,7 @@ pub fn compile_styles(indir: &str, outdir: &str) {
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Default)]
+#[derive(Serialize, Deserialize, PartialEq, Default, Clone)]
 pub enum Method {
     #[default]
     GET,
@@ -519,3 +1138,83 @@ impl Method {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curly_quotes_open_and_close() {
+        let quotes = Quotes::default();
+        assert_eq!(
+            curly_quotes(r#""Hello," she said."#, None, quotes),
+            "“Hello,” she said."
+        );
+        assert_eq!(curly_quotes("'single'", None, quotes), "‘single’");
+        assert_eq!(
+            curly_quotes(r#"("nested 'quotes'")"#, None, quotes),
+            "(“nested ‘quotes’”)"
+        );
+    }
+
+    #[test]
+    fn curly_quotes_apostrophes() {
+        let quotes = Quotes::default();
+        assert_eq!(curly_quotes("don't", None, quotes), "don’t");
+        assert_eq!(
+            curly_quotes("the dogs' bowls", None, quotes),
+            "the dogs’ bowls"
+        );
+    }
+
+    #[test]
+    fn curly_quotes_previous() {
+        let quotes = Quotes::default();
+        // The text continues a paragraph, e.g. after `*emphasis*`
+        assert_eq!(curly_quotes("\" and", Some('s'), quotes), "” and");
+        assert_eq!(curly_quotes("\"quote\"", Some(' '), quotes), "“quote”");
+        assert_eq!(curly_quotes("s\"", Some('\n'), quotes), "s”");
+    }
+
+    #[test]
+    fn curly_quotes_styles() {
+        assert_eq!(
+            curly_quotes(r#""a 'b'""#, None, Quotes::for_lang("fr")),
+            "«a ‹b›»"
+        );
+        assert_eq!(
+            curly_quotes(r#""a 'b'""#, None, Quotes::for_lang("de-AT")),
+            "„a ‚b‘“"
+        );
+        assert_eq!(
+            curly_quotes(r#""a""#, None, Quotes::for_lang("zh-Hant-TW")),
+            "「a」"
+        );
+        assert_eq!(
+            curly_quotes(r#""a""#, None, Quotes::for_lang("zh-CN")),
+            "“a”"
+        );
+        // A quote right after an opening one is an opening one too
+        assert_eq!(
+            curly_quotes(r#"«'a'»"#, None, Quotes::parse("«»").unwrap()),
+            "«‘a’»"
+        );
+        assert!(Quotes::parse("«").is_err());
+    }
+
+    #[test]
+    fn emojis_known_codes() {
+        assert_eq!(emojis(":cat: and :dog:"), "🐱 and 🐶");
+        assert_eq!(emojis(":cat::dog:"), "🐱🐶");
+        assert_eq!(emojis(":+1:"), "👍");
+    }
+
+    #[test]
+    fn emojis_leave_other_colons() {
+        assert_eq!(emojis("at 10:30:00"), "at 10:30:00");
+        assert_eq!(emojis(":not_an_emoji:"), ":not_an_emoji:");
+        assert_eq!(emojis("Note: a :cat:"), "Note: a 🐱");
+        assert_eq!(emojis("a :: b"), "a :: b");
+        assert_eq!(emojis("trailing:"), "trailing:");
+    }
+}
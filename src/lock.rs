@@ -0,0 +1,48 @@
+//! `cuteness.lock`, the exact template commit a project is built with, written by `cuteness update`.
+
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Name of the lock file, in the project's root directory.
+pub const LOCK_FILE: &str = "cuteness.lock";
+
+/// Template version used when `template_version` isn't set in `cuteconfig.toml`.
+pub const DEFAULT_TEMPLATE_VERSION: &str = "main";
+
+const HEADER: &str =
+    "# Generated by `cuteness update`, commit it so everyone builds with the same templates.\n\n";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Lock {
+    pub templates: LockedTemplates,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct LockedTemplates {
+    /// The version requested in `cuteconfig.toml` (tag, branch or commit)
+    pub version: String,
+    /// The commit it resolved to
+    pub commit: String,
+}
+
+impl Lock {
+    /// Reads the project's lock file, if there is one.
+    pub fn load() -> Result<Option<Self>> {
+        if !Path::new(LOCK_FILE).exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(LOCK_FILE)
+            .with_context(|| format!("Couldn't read `{LOCK_FILE}`"))?;
+        toml::from_str(&content)
+            .map(Some)
+            .with_context(|| format!("Couldn't parse `{LOCK_FILE}`"))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let content = toml::to_string(self).context("Couldn't serialize the lock file")?;
+        fs::write(LOCK_FILE, format!("{HEADER}{content}"))
+            .with_context(|| format!("Couldn't write `{LOCK_FILE}`"))
+    }
+}
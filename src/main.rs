@@ -1,21 +1,30 @@
 #![warn(clippy::all)]
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use clap::Parser as Parse;
-use cuteness::*;
-use handlebars::{handlebars_helper, no_escape};
-use hashbrown::HashMap;
+use cuteness::{
+    cache::*,
+    external::{External, Input},
+    highlight::{self, HighlightConfig, Highlighter},
+    lock::*,
+    math::{self, Formulas},
+    summary::*,
+    templates::Templates,
+    *,
+};
+use handlebars::{handlebars_helper, no_escape, Handlebars};
+use hashbrown::{HashMap, HashSet};
 use lazy_static::lazy_static;
-use pulldown_cmark::{html, Options, Parser};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use toml::Value;
 use walkdir::WalkDir;
 use yaml_front_matter::YamlFrontMatter;
 
-use std::fs::{self, canonicalize, read_dir, read_to_string, File};
-use std::io::{Read, Write};
-use std::path::Path;
+use std::fs::{self, canonicalize, read_to_string, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
 #[derive(Parse)]
 struct Args {
@@ -38,23 +47,153 @@ enum SCommand {
         #[cfg(feature = "sass")]
         #[arg(long, default_value = "sass")]
         sassbin: String,
+        /// Keep running and rebuild whatever changes in `src`, `SUMMARY.toml` or `cuteconfig.toml`
+        #[arg(long)]
+        watch: bool,
+        /// What to generate: the pages and a web-server (`server`), or only pages for static hosting (`static`)
+        #[arg(long, value_enum, default_value_t)]
+        target: Target,
+        /// With `--target static`, write `introduction/index.html` (`pretty`) or `introduction.html` (`html`)
+        #[arg(long, value_enum, default_value_t)]
+        urls: UrlStyle,
+    },
+    /// Builds your `src` directory and serves it, rebuilding and reloading the browser on every change
+    Serve {
+        /// Connection port
+        #[arg(long, default_value = "8080")]
+        port: u16,
+        /// Output directory
+        #[arg(long, default_value = "www")]
+        outdir: String,
+        /// Command for the sass compiler. E.g. "sass"
+        #[cfg(feature = "sass")]
+        #[arg(long, default_value = "sass")]
+        sassbin: String,
     },
     /// Initializes the necessary files (configuration, placeholders...), ready to be modified.
     Init,
-    /// Updates the internal configuration files in the configuration path; this is an enhanced `git pull`.
-    Update,
+    /// Updates the internal configuration files in the configuration path to the version in `cuteconfig.toml`; this is an enhanced `git pull`.
+    Update {
+        /// Check out the commit in `cuteness.lock` instead of resolving the version again
+        #[arg(long)]
+        locked: bool,
+        /// Install the templates from a local directory or tarball instead of the repository
+        #[arg(long, conflicts_with = "locked")]
+        from: Option<PathBuf>,
+    },
     /// Creates the necessary configuration directory and its internal files; this is an enhanced `git clone`.
-    Setup,
+    Setup {
+        /// Install the templates from a local directory or tarball instead of the repository
+        #[arg(long)]
+        from: Option<PathBuf>,
+    },
+    /// Checks that everything needed to build the project is in place
+    Doctor {
+        /// Output directory
+        #[arg(long, default_value = "www")]
+        outdir: String,
+        /// Command for the sass compiler. E.g. "sass"
+        #[cfg(feature = "sass")]
+        #[arg(long, default_value = "sass")]
+        sassbin: String,
+    },
     /// Deletes the `www` directory
     Clean,
+    /// Manages the themes installed in the configuration path
+    Theme {
+        #[command(subcommand)]
+        command: ThemeCommand,
+    },
     /// Deletes all configuration files. `cargo uninstall` will not remove these, so before using `cargo uninstall`, use this command.
     Uninstall,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Default, Debug)]
+enum Target {
+    /// Pages in `<outdir>/static` and the Rocket project that serves them in `<outdir>/routing`
+    #[default]
+    Server,
+    /// Only pages and styles, at the root of `<outdir>`, ready to be uploaded to any static hosting
+    Static,
+}
+
+/// Where pages are written with `--target static`
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Default, Debug)]
+enum UrlStyle {
+    /// `introduction/index.html`, served at `/introduction/`
+    #[default]
+    Pretty,
+    /// `introduction.html`
+    Html,
+}
+
+#[derive(clap::Subcommand)]
+enum ThemeCommand {
+    /// Lists the installed themes
+    List,
+    /// Installs a theme from a local directory or a git repository url
+    Add { source: String },
+    /// Selects an installed theme in this project's `cuteconfig.toml`
+    Use { name: String },
+    /// Uninstalls a theme
+    Remove { name: String },
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct Config {
     config: HashMap<String, Value>,
     misc: MiscConfig,
+    #[serde(default)]
+    validation: ValidationConfig,
+    #[serde(default)]
+    routing: RoutingConfig,
+    #[serde(default)]
+    highlighting: HighlightConfig,
+    /// Kept in order, it's the order the preprocessors run in
+    #[serde(default)]
+    preprocessors: toml::Table,
+}
+
+/// `[routing]` in `cuteconfig.toml`
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct RoutingConfig {
+    #[serde(default)]
+    backend: Backend,
+    /// Go code run before the server starts (`go` backend)
+    init_behaviour: Option<String>,
+    /// Go code run if the server fails, with the error in `err` (`go` backend)
+    fail_behaviour: Option<String>,
+    /// Go packages imported by `init_behaviour` and `fail_behaviour` (`go` backend)
+    #[serde(default)]
+    imports: Vec<String>,
+    /// Reverse-proxy configurations to generate in `proxy_dir`
+    #[serde(default)]
+    proxies: Vec<Proxy>,
+    /// Where the reverse-proxy configurations are written, `<outdir>/proxy` by default (only for the server target)
+    proxy_dir: Option<PathBuf>,
+}
+
+/// A reverse-proxy configuration that serves the pages without the generated web-server
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Proxy {
+    /// `nginx.conf`, a `server` block
+    Nginx,
+    /// `Caddyfile`
+    Caddy,
+}
+
+/// The web-server generated in `<outdir>/routing`
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Backend {
+    /// A Rocket project, run with `cargo run`
+    #[default]
+    Rocket,
+    /// An Axum project, run with `cargo run` on the stable toolchain
+    Axum,
+    /// A single `main.go`, run with `go run`
+    Go,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -63,9 +202,15 @@ struct MiscConfig {
     html_lang: Option<String>,
     additional_html_header: Option<String>,
     syntax_highlighting: Option<bool>,
+    /// Generates the search index and shows the search box (default: `true`)
+    search: Option<bool>,
+    /// Name of the theme in `CONFIG_PATH/themes` to use
+    theme: Option<String>,
+    /// Tag, branch or commit of the templates to use (default: `main`)
+    template_version: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct PageConfig {
     title: String,
     pageconf: Option<HashMap<String, Value>>,
@@ -73,23 +218,49 @@ struct PageConfig {
     #[serde(default)]
     method: Method,
     params: Option<Vec<Param>>,
+    /// `false` disables every preprocessor in this page, a map (`emojis: false`) disables some of them
+    preprocessors: Option<PagePreprocessors>,
+}
+
+/// `preprocessors` in a page's front-matter
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+enum PagePreprocessors {
+    All(bool),
+    Each(HashMap<String, bool>),
+}
+
+impl PagePreprocessors {
+    fn enabled(&self, name: &str) -> bool {
+        match self {
+            PagePreprocessors::All(enabled) => *enabled,
+            PagePreprocessors::Each(preprocessors) => {
+                preprocessors.get(name).copied().unwrap_or(true)
+            }
+        }
+    }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct Page {
     config: PageConfig,
     path: String,
+    search: search::Entry,
 }
 
-#[derive(Serialize, Deserialize)]
-struct SummaryConfig {
-    map: Vec<Map>,
+impl Page {
+    fn url(&self) -> String {
+        page_url(Path::new(&self.path))
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-struct Map {
-    title: String,
-    url: String,
+/// The url the generated router serves the page at `path` at (its path in `src`, without `.md`)
+fn page_url(path: &Path) -> String {
+    path.strip_prefix("src")
+        .unwrap_or(path)
+        .with_extension("")
+        .to_string_lossy()
+        .replace('\\', "/")
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -100,11 +271,6 @@ struct Param {
 
 fn main() -> Result<()> {
     let args = Args::parse();
-    // * Check for updates =======================
-
-    if !Path::new(CONFIG_PATH.as_path()).exists() {
-        setup();
-    }
 
     if let Some(subcommand) = args.command {
         match subcommand {
@@ -112,11 +278,21 @@ fn main() -> Result<()> {
                 port,
                 outdir,
                 sassbin,
-            } => build(port, Path::new(&outdir), sassbin)?,
+                watch,
+                target,
+                urls,
+            } => build(port, Path::new(&outdir), sassbin, watch, target, urls)?,
+            SCommand::Serve {
+                port,
+                outdir,
+                sassbin,
+            } => serve(port, Path::new(&outdir), sassbin)?,
             SCommand::Init => init(),
-            SCommand::Update => check_for_updates(),
+            SCommand::Update { locked, from } => update(locked, from.as_deref())?,
             SCommand::Uninstall => uninstall(),
-            SCommand::Setup => setup(),
+            SCommand::Setup { from } => setup(from.as_deref())?,
+            SCommand::Theme { command } => theme_command(command)?,
+            SCommand::Doctor { outdir, sassbin } => doctor(Path::new(&outdir), &sassbin)?,
             SCommand::Clean => {
                 fs::remove_dir_all("www").context("Couldn't remove directory `www`")?
             }
@@ -125,270 +301,818 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn build(port: u16, outdir: &Path, sassbin: String) -> Result<()> {
-    // * Register all templates and helpers ======
-
-    dbg!(CONFIG_PATH.display());
+fn theme_command(command: ThemeCommand) -> Result<()> {
+    match command {
+        ThemeCommand::List => {
+            let themes = theme::list()?;
+            if themes.is_empty() {
+                println!(
+                    "No themes installed, install one with `cuteness theme add <path or git url>`"
+                );
+            }
+            for theme in themes {
+                println!(
+                    "{} {} (requires cuteness {})",
+                    theme.name, theme.version, theme.cuteness
+                );
+            }
+        }
+        ThemeCommand::Add { source } => {
+            let theme = theme::add(&source)?;
+            println!(
+                "Installed theme `{}` {}, select it with `cuteness theme use {}`",
+                theme.name, theme.version, theme.name
+            );
+        }
+        ThemeCommand::Use { name } => {
+            theme::select(&name)?;
+            println!("Now using theme `{name}`");
+        }
+        ThemeCommand::Remove { name } => {
+            theme::remove(&name)?;
+            println!("Removed theme `{name}`");
+        }
+    }
+    Ok(())
+}
 
-    let mut reg = handlebars::Handlebars::new();
-    reg.register_escape_fn(no_escape);
-    reg.register_template_file(
-        "page_template",
-        CONFIG_PATH.join("templates").join("page.html.hbs"),
-    )
-    .context("Couldn't register page.html.hbs")?;
-    reg.register_template_file(
-        "rocket_routing_template",
-        CONFIG_PATH
-            .join("templates")
-            .join("routing")
-            .join("src")
-            .join("main.rs.hbs"),
-    )
-    .context("Couldn't register `templates/routing/src/main.rs.hbs`")?;
+/// Checks out the templates at the version pinned in `cuteconfig.toml` and records the commit in `cuteness.lock`. Outside of a project, it just updates to the latest templates.
+fn update(locked: bool, from: Option<&Path>) -> Result<()> {
+    if let Some(source) = from {
+        if !CONFIG_PATH.join("templates").exists() {
+            bail!(
+                "Cuteness isn't configured yet, run `cuteness setup --from {}` instead",
+                source.display()
+            );
+        }
+        install_local(source)?;
+        println!("Templates updated from `{}`!", source.display());
+        return Ok(());
+    }
 
-    reg.register_template_file(
-        "rocket_toml",
-        CONFIG_PATH
-            .join("templates")
-            .join("routing")
-            .join("Rocket.toml.hbs"),
-    )
-    .context("Couldn't register Rocket.toml.hbs")?;
+    if !Path::new("cuteconfig.toml").exists() {
+        if locked {
+            bail!("`--locked` can only be used in a project (couldn't find `cuteconfig.toml`)");
+        }
+        check_for_updates(DEFAULT_TEMPLATE_VERSION)?;
+        return Ok(());
+    }
 
-    handlebars_helper!(lower: |method: String| method.to_lowercase());
-    reg.register_helper("lower", Box::new(lower));
+    let (config, _) = read_configuration()?;
+    let version = config
+        .misc
+        .template_version
+        .unwrap_or_else(|| DEFAULT_TEMPLATE_VERSION.to_string());
 
-    handlebars_helper!(file_name: |path: String| {
-        let name = Path::new(&path).file_name().unwrap().to_str().unwrap();
-        &name[..name.len() - 3]
-    });
+    if locked {
+        let Some(lock) = Lock::load()? else {
+            bail!("Couldn't find `{LOCK_FILE}`, run `cuteness update` to create it");
+        };
+        if lock.templates.version != version {
+            bail!(
+                "`{LOCK_FILE}` is for template version `{}`, but `cuteconfig.toml` asks for `{version}`; run `cuteness update` to update it",
+                lock.templates.version
+            );
+        }
+        check_for_updates(&lock.templates.commit)?;
+        return Ok(());
+    }
 
-    reg.register_helper("file_name", Box::new(file_name));
+    let commit = check_for_updates(&version)?;
+    Lock {
+        templates: LockedTemplates { version, commit },
+    }
+    .save()
+}
 
-    handlebars_helper!(sanitize: |path: String| {
+/// Warns if the installed templates aren't the ones pinned in `cuteness.lock`.
+fn check_lock(config: &Config) -> Result<()> {
+    if let Some(problem) = lock_mismatch(config)? {
+        eprintln!("Warning: {problem}");
+    }
+    Ok(())
+}
 
-        lazy_static!{
-            static ref RE: regex::Regex = regex::Regex::new("([<>])").unwrap();
-        };
+/// Describes how the installed templates differ from the ones pinned in `cuteness.lock`, if they do.
+fn lock_mismatch(config: &Config) -> Result<Option<String>> {
+    // `git` is only needed to compare against a lock file, a project without one builds without it
+    let Some(lock) = Lock::load()? else {
+        return Ok(None);
+    };
+    let commit = match templates_commit() {
+        Ok(Some(commit)) => commit,
+        Ok(None) => return Ok(None),
+        Err(e) => {
+            return Ok(Some(format!(
+                "couldn't check the installed templates against `{LOCK_FILE}`: {e:#}"
+            )))
+        }
+    };
 
-        RE.replace_all(&path, "_").to_string()
-    });
-    reg.register_helper("sanitize", Box::new(sanitize));
+    let version = config
+        .misc
+        .template_version
+        .as_deref()
+        .unwrap_or(DEFAULT_TEMPLATE_VERSION);
+    Ok(if lock.templates.version != version {
+        Some(format!(
+            "`cuteconfig.toml` asks for template version `{version}`, but `{LOCK_FILE}` is for `{}`; run `cuteness update`",
+            lock.templates.version
+        ))
+    } else if lock.templates.commit != commit {
+        Some(format!(
+            "the installed templates ({commit}) aren't the ones in `{LOCK_FILE}` ({}); run `cuteness update --locked`",
+            lock.templates.commit
+        ))
+    } else {
+        None
+    })
+}
 
-    handlebars_helper!(contains: |src: String, search: String| { src.contains(&search)});
-    reg.register_helper("contains", Box::new(contains));
+fn build(
+    port: u16,
+    outdir: &Path,
+    sassbin: String,
+    keep_watching: bool,
+    target: Target,
+    urls: UrlStyle,
+) -> Result<()> {
+    let mut site = Site::new(port, outdir, sassbin, target, urls)?;
+    site.build()?;
+
+    if keep_watching {
+        watch(&mut site, || println!("Rebuilt!"))?;
+    }
+    Ok(())
+}
 
-    handlebars_helper!(is_pure: |src: String| {
-        lazy_static!{
-            static ref RE: regex::Regex = regex::Regex::new("([<>])").unwrap();
-        };
+/// Everything needed to render the project, kept between rebuilds so watch mode can re-run only the affected stages.
+struct Site<'a> {
+    reg: Handlebars<'static>,
+    templates: Templates,
+    /// `None` unless `syntax_highlighting` is enabled
+    highlighter: Option<Highlighter>,
+    /// Enabled preprocessors, in the order they run
+    preprocessors: Preprocessors,
+    config: Config,
+    summary: SummaryConfig,
+    pages: Vec<Page>,
+    cache: Manifest<Page>,
+    port: u16,
+    outdir: &'a Path,
+    #[cfg_attr(not(feature = "sass"), allow(dead_code))]
+    sassbin: String,
+    target: Target,
+    urls: UrlStyle,
+}
 
-        !RE.is_match(&src)
-    });
-    reg.register_helper("is_pure", Box::new(is_pure));
+impl<'a> Site<'a> {
+    fn new(
+        port: u16,
+        outdir: &'a Path,
+        sassbin: String,
+        target: Target,
+        urls: UrlStyle,
+    ) -> Result<Self> {
+        dbg!(CONFIG_PATH.display());
+
+        let (config, summary) = read_configuration()?;
+        check_lock(&config)?;
+        let templates = Templates::new(config.misc.theme.as_deref())?;
+
+        Ok(Site {
+            reg: registry(&templates)?,
+            templates,
+            highlighter: highlighter(&config)?,
+            preprocessors: preprocessors(&config)?,
+            config,
+            summary,
+            pages: Vec::new(),
+            // The output directory of a static site gets published as it is, so its manifest is only kept in memory
+            cache: match target {
+                Target::Server => Manifest::load(outdir),
+                Target::Static => Manifest::default(),
+            },
+            port,
+            outdir,
+            sassbin,
+            target,
+            urls,
+        })
+    }
 
-    handlebars_helper!(cut_end: |src: String, to_cut: usize| {
-        &src[..src.len() - to_cut]
-    });
+    /// Runs every stage of the build.
+    fn build(&mut self) -> Result<()> {
+        self.create_directories()?;
+        self.render_pages()?;
+        self.write_search_index()?;
+        self.write_routing()?;
+        self.write_proxies()?;
+        self.styles()?;
+        self.save_cache()?;
+        self.validate_summary()
+    }
 
-    reg.register_helper("cut_end", Box::new(cut_end));
+    /// Re-runs only the stages affected by `changed`: a Markdown file re-renders that page, `SUMMARY.toml` or `cuteconfig.toml` re-render every page and `src/styles` recompiles the styles.
+    fn rebuild(&mut self, changed: &[PathBuf]) -> Result<()> {
+        let mut everything = false;
+        let mut styles = false;
+        let mut pages = Vec::new();
+
+        for path in changed {
+            if path == Path::new("SUMMARY.toml")
+                || path == Path::new("cuteconfig.toml")
+                || path.starts_with(templates::PROJECT_TEMPLATES)
+            {
+                everything = true;
+            } else if path.starts_with("src/styles") {
+                styles = true;
+            } else if path.to_string_lossy().ends_with(".md") && !self.skipped(path) {
+                pages.push(path);
+            }
+        }
 
-    handlebars_helper!(cut_start: |src: String, to_cut: usize| {
-        &src[to_cut..]
-    });
+        if everything {
+            (self.config, self.summary) = read_configuration()?;
+            self.templates = Templates::new(self.config.misc.theme.as_deref())?;
+            self.reg = registry(&self.templates)?;
+            self.highlighter = highlighter(&self.config)?;
+            self.preprocessors = preprocessors(&self.config)?;
+            self.create_directories()?;
+            self.render_pages()?;
+            self.write_search_index()?;
+            self.write_routing()?;
+            self.write_proxies()?;
+            self.styles()?;
+        } else if !pages.is_empty() {
+            for &path in &pages {
+                let position = self
+                    .pages
+                    .iter()
+                    .position(|page| Path::new(&page.path) == path);
+                if path.exists() {
+                    let (page, hash) = self.page(path)?;
+                    self.cache.insert(path.to_path_buf(), hash, page.clone());
+                    match position {
+                        Some(i) => self.pages[i] = page,
+                        None => self.pages.push(page),
+                    }
+                } else {
+                    if let Some(i) = position {
+                        self.pages.remove(i);
+                    }
+                    self.cache.remove(path);
+                    let html = self.html_path(path);
+                    if html.exists() {
+                        fs::remove_file(&html).with_context(|| {
+                            format!("Couldn't remove file `{}`", html.display())
+                        })?;
+                    }
+                }
+            }
+            self.write_search_index()?;
+            self.write_routing()?;
+        }
 
-    reg.register_helper("cut_start", Box::new(cut_start));
+        if styles {
+            self.styles()?;
+        }
 
-    // ===========================================
+        self.save_cache()?;
 
-    // ===========================================
+        if everything || !pages.is_empty() {
+            self.validate_summary()?;
+        }
+        Ok(())
+    }
 
-    // * Read configuration ========================
+    /// Checks that every entry in `SUMMARY.toml` has a page, and that every page is in `SUMMARY.toml`
+    fn validate_summary(&self) -> Result<()> {
+        let pages: Vec<(String, String)> = self
+            .pages
+            .iter()
+            // Pages with parameters can't be linked directly
+            .filter(|page| params_in_path(Path::new(&page.path)).is_empty())
+            .map(|page| (page.url(), page.path.clone()))
+            .collect();
+
+        self.summary
+            .validate_pages(&pages, &self.config.validation)
+            .context("`SUMMARY.toml` doesn't match the pages in `src`")
+    }
 
-    let mut content = String::new();
-    if !Path::new("cuteconfig.toml").exists() {
-        panic!("Couldn't find cuteconfig.toml");
+    /// Saves what the next build needs: the manifest for the server, the list of generated files for a static site. A static build also removes the files the previous one generated and this one didn't (e.g. deleted pages).
+    fn save_cache(&self) -> Result<()> {
+        match self.target {
+            Target::Server => self.cache.save(self.outdir),
+            Target::Static => {
+                let files = self.output_files();
+                let stale: Vec<PathBuf> = load_output_list(self.outdir)
+                    .into_iter()
+                    .filter(|file| !files.contains(file))
+                    .collect();
+                remove_outputs(self.outdir, &stale)?;
+                save_output_list(self.outdir, &files)
+            }
+        }
     }
 
-    let mut f = File::open("cuteconfig.toml").context("Couldn't open `cuteconfig.toml`")?;
-    f.read_to_string(&mut content)
-        .context("Couldn't read configuration `cuteconfig.toml`")?;
+    /// With `--target static`, every file the build writes, relative to the output directory.
+    fn output_files(&self) -> Vec<PathBuf> {
+        let site_dir = self.site_dir();
+        let styles_dir = site_dir.join("styles");
+        let mut files: Vec<PathBuf> = self
+            .pages
+            .iter()
+            .map(|page| self.html_path(Path::new(&page.path)))
+            .collect();
+        if self.config.misc.search != Some(false) {
+            files.push(site_dir.join(search::INDEX_FILE));
+        }
 
-    let config = toml::from_str::<Config>(&content).context("Couldn't parse configuration")?;
+        // The styles compiled from `src/styles`, except partials, which aren't compiled
+        for entry in WalkDir::new("src/styles")
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| !e.file_name().to_string_lossy().starts_with('_'))
+        {
+            let style = entry.path().strip_prefix("src/styles").unwrap();
+            let css = styles_dir.join(style).with_extension("css");
+            files.push(css.with_extension("css.map"));
+            files.push(css);
+        }
+        files.extend(
+            self.templates
+                .styles()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, _)| styles_dir.join(name)),
+        );
+        if self.highlighter.is_some() {
+            files.push(styles_dir.join(highlight::STYLESHEET));
+        }
 
-    // * Create output directory ======================
+        let mut files: Vec<PathBuf> = files
+            .into_iter()
+            .filter_map(|file| file.strip_prefix(self.outdir).ok().map(Path::to_path_buf))
+            .collect();
+        files.sort();
+        files.dedup();
+        files
+    }
 
-    if !Path::new(outdir).exists() {
-        fs::create_dir(outdir)
-            .with_context(|| format!("Couldn't create directory {}", outdir.display()))?;
-    };
+    /// Removes what a build for the other target left in the output directory, so a static site isn't published with the server's files, and the server doesn't serve a static site's pages. Only what a build is known to have written is removed: the server's directories if there's a server manifest, and the files in the list of a static build.
+    fn remove_leftovers(&self) -> Result<()> {
+        let outdir = self.outdir;
+        let mut leftovers = Vec::new();
+
+        match self.target {
+            Target::Static if outdir.join(MANIFEST_FILE).is_file() => {
+                leftovers.push(outdir.join(MANIFEST_FILE));
+                // The proxy configurations used to be written in the output directory
+                leftovers.extend(
+                    ["routing", "static", "proxy", "nginx.conf", "Caddyfile"]
+                        .map(|file| outdir.join(file)),
+                );
+            }
+            Target::Server if outdir.join(OUTPUT_LIST).is_file() => {
+                remove_outputs(outdir, &load_output_list(outdir))?;
+                leftovers.push(outdir.join(OUTPUT_LIST));
+            }
+            _ => {}
+        }
 
-    // ===========================================
+        for path in leftovers {
+            let removed = if path.is_dir() {
+                fs::remove_dir_all(&path)
+            } else if path.exists() {
+                fs::remove_file(&path)
+            } else {
+                continue;
+            };
+            removed.with_context(|| format!("Couldn't remove `{}`", path.display()))?;
+        }
+        Ok(())
+    }
 
-    // * Create Cargo project
+    fn create_directories(&self) -> Result<()> {
+        let outdir = self.outdir;
 
-    let binding = outdir.join("routing");
-    let cargo_project = Path::new(&binding);
+        // * Create output directory ======================
 
-    {
-        let routing_path = Path::new(&outdir).join("routing");
-        if !routing_path.exists() {
-            fs::create_dir(&routing_path).context("Couldn't create directory `routing`")?;
+        if !Path::new(outdir).exists() {
+            fs::create_dir(outdir)
+                .with_context(|| format!("Couldn't create directory {}", outdir.display()))?;
         };
-        if !routing_path.join("src").exists() {
-            fs::create_dir(routing_path.join("src"))
-                .context("Couldn't create directory `routing/src`")?;
+
+        self.remove_leftovers()?;
+
+        // ===========================================
+
+        if self.target == Target::Static {
+            return Ok(());
+        }
+
+        // * Create Cargo project
+
+        {
+            let routing_path = Path::new(&outdir).join("routing");
+            if !routing_path.exists() {
+                fs::create_dir(&routing_path).context("Couldn't create directory `routing`")?;
+            };
+            if self.config.routing.backend != Backend::Go && !routing_path.join("src").exists() {
+                fs::create_dir(routing_path.join("src"))
+                    .context("Couldn't create directory `routing/src`")?;
+            };
         };
-    };
 
-    {
-        let mut f = File::create(cargo_project.join("Cargo.toml")).with_context(|| {
-            format!(
-                "Couldn't create | open file {}/Cargo.toml",
-                outdir.display()
-            )
-        })?;
+        // ===========================================
 
-        f.write_all(
-            fs::read_to_string(
-                CONFIG_PATH
-                    .join("templates")
-                    .join("routing")
-                    .join("Cargo.toml"),
-            )
-            .unwrap_or_else(|e| {
-                panic!(
-                    "Couldn't open file `{}`/templates/routing/Cargo.toml: {e}",
-                    CONFIG_PATH.display()
+        // * Create `www` directory
+
+        if !Path::new(&outdir.join("static")).exists() {
+            fs::create_dir(outdir.join("static")).with_context(|| {
+                format!(
+                    "Couldn't create directory `{}`",
+                    outdir.join("static").display()
                 )
-            })
-            .as_bytes(),
-        )
-        .context("Couldn't write to routing file")?;
+            })?;
+        }
+
+        Ok(())
     }
 
-    let mut f = File::create(cargo_project.join("src").join("main.rs")).with_context(|| {
-        format!(
-            "Couldn't create | open file {}/src/main.rs",
-            outdir.display()
-        )
-    })?;
+    /// Renders every Markdown file in `src` whose inputs changed since the last build.
+    fn render_pages(&mut self) -> Result<()> {
+        // Everything every page depends on, if any of these changes, all pages get rendered again
+        let mut inputs = vec![
+            PathBuf::from("cuteconfig.toml"),
+            PathBuf::from("SUMMARY.toml"),
+        ];
+        inputs.extend_from_slice(self.templates.dirs());
+        self.cache.set_global(hash_files(&inputs)?);
+
+        // Sorted, so pages are always rendered and reported in the same order
+        let paths: Vec<PathBuf> = WalkDir::new("src")
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".md"))
+            .map(|e| e.into_path())
+            .filter(|path| !self.skipped(path))
+            .collect();
+
+        // Every page is written to `static/<file name>.html`, so two pages with the same file name would overwrite each other
+        {
+            let mut outputs: HashMap<PathBuf, &Path> = HashMap::new();
+            for path in &paths {
+                if let Some(other) = outputs.insert(self.html_path(path), path) {
+                    bail!(
+                        "`{}` and `{}` would both be written to `{}`",
+                        other.display(),
+                        path.display(),
+                        self.html_path(path).display()
+                    );
+                }
+            }
+        }
 
-    // ===========================================
+        // Results are collected in the original order (not the order they finish), so the reported error is always the one of the first failing page
+        let rendered: Vec<Result<(Page, String)>> =
+            paths.par_iter().map(|path| self.page(path)).collect();
 
-    // * Generate sidebar from SUMMARY.toml
+        let mut pages = Vec::with_capacity(rendered.len());
+        for result in rendered {
+            let (page, hash) = result?;
+            self.cache
+                .insert(PathBuf::from(&page.path), hash, page.clone());
+            pages.push(page);
+        }
 
-    if !Path::new("SUMMARY.toml").exists() {
-        panic!("Couldn't find SUMMARY.toml");
+        {
+            let sources: HashSet<&Path> = pages.iter().map(|page| Path::new(&page.path)).collect();
+            self.cache.retain(|path| sources.contains(path));
+        }
+        self.pages = pages;
+        Ok(())
     }
 
-    let summary: SummaryConfig = toml::from_str(
-        &read_to_string("SUMMARY.toml").context("Couldn't get file `SUMMARY.toml`")?,
-    )
-    .context("Couldn't parse summary in `SUMMARY.toml`")?;
+    /// Pages with parameters (e.g. `src/user/<id>.md`) need the server, so they're left out of static sites.
+    fn skipped(&self, path: &Path) -> bool {
+        let skipped = self.target == Target::Static && !params_in_path(path).is_empty();
+        if skipped {
+            eprintln!(
+                "Warning: `{}` has parameters, which need `--target server`; it's left out of the static site",
+                path.display()
+            );
+        }
+        skipped
+    }
 
-    // ===========================================
+    /// Gets the page at `path` and the hash of its source, only rendering it if its source changed since the last build. With external preprocessors, every page is always rendered, as they can depend on anything.
+    fn page(&self, path: &Path) -> Result<(Page, String)> {
+        let content =
+            fs::read_to_string(path).context("Can't get path of file in the input directory")?;
+        let hash = blake3::hash(content.as_bytes()).to_hex().to_string();
 
-    // * Create `www` directory and loop each item
+        let cached = match self.preprocessors.external.is_empty() {
+            true => self.cache.get(path, &hash),
+            false => None,
+        };
+        if let Some(page) = cached {
+            if self.html_path(path).exists() {
+                return Ok((page, hash));
+            }
+        }
 
-    if !Path::new(&outdir).exists() {
-        fs::create_dir(outdir)
-            .with_context(|| format!("Couldn't create directory `{}`", outdir.display()))?;
+        let page = self
+            .render_page(path, &content)
+            .with_context(|| format!("Couldn't build page `{}`", path.display()))?;
+        Ok((page, hash))
     }
 
-    if !Path::new(&outdir.join("static")).exists() {
-        fs::create_dir(&outdir.join("static")).with_context(|| {
-            format!(
-                "Couldn't create directory `{}`",
-                outdir.join("static").display()
-            )
-        })?;
+    /// Path of the HTML file generated from the Markdown file at `path`. The server's pages keep the layout of `src` (`src/guide/intro.md` is `static/guide/intro.html`), as every router serves `/guide/intro` from there.
+    fn html_path(&self, path: &Path) -> PathBuf {
+        match self.target {
+            Target::Server => self.site_dir().join(format!("{}.html", page_url(path))),
+            Target::Static => self.outdir.join(self.static_url(&page_url(path))),
+        }
     }
 
-    // let paths = fs::read_dir("src").context("Couldn't read directory `src`")?;
+    /// The directory pages, styles and the search index are written to.
+    fn site_dir(&self) -> PathBuf {
+        match self.target {
+            Target::Server => self.outdir.join("static"),
+            Target::Static => self.outdir.to_path_buf(),
+        }
+    }
 
-    let mut pages = Vec::new();
+    /// The directory the reverse-proxy configurations are written to: `proxy_dir` in `[routing]`, or `<outdir>/proxy` for the server. They contain paths of the server that shouldn't be published, so there's no default for a static site, as its whole output directory is.
+    fn proxy_dir(&self) -> Option<PathBuf> {
+        match (&self.config.routing.proxy_dir, self.target) {
+            (Some(dir), _) => Some(dir.clone()),
+            (None, Target::Server) => Some(self.outdir.join("proxy")),
+            (None, Target::Static) => None,
+        }
+    }
 
-    for path in WalkDir::new("src").into_iter().filter_map(|e| e.ok()) {
-        // * Convert Markdown file to HTML =========
+    /// With `--target static`, the page at `url` relative to the root of the site: `introduction/index.html` or `introduction.html`.
+    fn static_url(&self, url: &str) -> String {
+        match self.urls {
+            UrlStyle::Html => format!("{url}.html"),
+            // Static hosts serve `/404.html` for missing pages
+            UrlStyle::Pretty if url == "index" || url.ends_with("/index") || url == "404" => {
+                format!("{url}.html")
+            }
+            UrlStyle::Pretty => format!("{url}/index.html"),
+        }
+    }
 
-        if !path.file_name().to_string_lossy().ends_with(".md") {
-            continue;
+    /// The path from the page at `url` to the root of the site (e.g. `../`). The server serves `guide/intro` at `/guide/intro`, so it's one level deep.
+    fn root(&self, url: &str) -> String {
+        let depth = match self.target {
+            Target::Server => url.matches('/').count(),
+            Target::Static => self.static_url(url).matches('/').count(),
         };
+        "../".repeat(depth)
+    }
 
-        let content = fs::read_to_string(path.path())
-            .context("Can't get path of file in the input directory")?;
+    /// Where a link to `url` (a page's url from the root of the site, optionally with a `#fragment`) has to point from the page at `from`. Links to other sites are left as they are, and links to other files are made relative to the root of the site.
+    fn link(&self, from: &str, url: &str) -> String {
+        if is_external(url) || url.starts_with('/') || url.starts_with('#') {
+            return url.to_string();
+        }
 
-        let parsed_markdown = YamlFrontMatter::parse::<PageConfig>(&content)
-            .expect("Couldn't parse frontmatter metadata");
+        let path_end = url.find(['#', '?']).unwrap_or(url.len());
+        let (path, fragment) = url.split_at(path_end);
+        let page = normalize_url(path);
+        if page.is_empty() {
+            return url.to_string();
+        }
 
-        let mut binding = curly_quotes(&parsed_markdown.content).to_string();
-        binding = emojis(&binding);
-        let parser = Parser::new_ext(&binding, Options::all());
+        let mut link = self.root(from);
+        if page.contains('.') {
+            link.push_str(url);
+            return link;
+        }
+        link.push_str(&self.site_link(&page));
+        if link.is_empty() {
+            link.push_str("./");
+        }
+        link.push_str(fragment);
+        link
+    }
+
+    /// Where a link written in the page at `from` has to point. Relative links are resolved against the page's directory, like the browser does with the server's urls, so `../introduction` in `guide/intro` links to `introduction`.
+    fn page_link(&self, from: &str, url: &str) -> String {
+        if is_external(url) || url.starts_with('/') || url.starts_with('#') {
+            return url.to_string();
+        }
+
+        let path_end = url.find(['#', '?']).unwrap_or(url.len());
+        let (path, fragment) = url.split_at(path_end);
+        if path.is_empty() {
+            return url.to_string();
+        }
+        let dir = from.rsplit_once('/').map_or("", |(dir, _)| dir);
+        match resolve_url(dir, path) {
+            Some(path) => self.link(from, &format!("{path}{fragment}")),
+            None => {
+                eprintln!("Warning: the link to `{url}` in `src/{from}.md` goes above the root of the site, it's left as it is");
+                url.to_string()
+            }
+        }
+    }
 
-        let mut html_output = String::new();
-        html::push_html(&mut html_output, parser);
+    /// The link to the page at `url` from the root of the site: the url itself for the server, `introduction/` or `introduction.html` with `--target static`. Directories are linked instead of their `index.html`, as static hosts serve it for them.
+    fn site_link(&self, url: &str) -> String {
+        if self.target == Target::Server {
+            return url.to_string();
+        }
+        let target = self.static_url(url);
+        match target.strip_suffix("index.html") {
+            Some(directory) if directory.is_empty() || directory.ends_with('/') => {
+                directory.to_string()
+            }
+            _ => target,
+        }
+    }
 
-        let path_filename = path.file_name();
-        let filename_str = path_filename.to_string_lossy();
+    /// The preprocessors that run on the page at `path`, leaving out the ones its front-matter disables.
+    fn page_preprocessors(
+        &self,
+        path: &Path,
+        page: &PageConfig,
+    ) -> Result<(Vec<&External>, Vec<&dyn Preprocessor>)> {
+        let enabled = |name: &str| {
+            page.preprocessors
+                .as_ref()
+                .is_none_or(|enabled| enabled.enabled(name))
+        };
+        if let Some(PagePreprocessors::Each(names)) = &page.preprocessors {
+            for name in names.keys() {
+                if !BUILTIN_PREPROCESSORS.contains(&name.as_str())
+                    && !self.config.preprocessors.contains_key(name)
+                {
+                    bail!(
+                        "Unknown preprocessor `{name}` in the front-matter of `{}`",
+                        path.display()
+                    );
+                }
+            }
+        }
 
-        let mut f = File::create(format!(
-            "{}/static/{}.html",
-            outdir.display(),
-            &filename_str[..filename_str.len() - 3]
+        Ok((
+            self.preprocessors
+                .external
+                .iter()
+                .filter(|preprocessor| enabled(preprocessor.name()))
+                .collect(),
+            self.preprocessors
+                .builtin
+                .iter()
+                .filter(|preprocessor| enabled(preprocessor.name()))
+                .map(AsRef::as_ref)
+                .collect(),
         ))
-        .with_context(|| {
-            format!(
-                "Couldn't create / open file `{}/static/{}.html`",
-                outdir.display(),
-                &filename_str[..filename_str.len() - 3]
+    }
+
+    fn render_page(&self, path: &Path, content: &str) -> Result<Page> {
+        // * Convert Markdown file to HTML =========
+
+        let parsed_markdown = YamlFrontMatter::parse::<PageConfig>(content).map_err(|e| {
+            anyhow!(
+                "Couldn't parse frontmatter metadata in `{}`: {e}",
+                path.display()
             )
         })?;
 
+        let (external, preprocessors) = self.page_preprocessors(path, &parsed_markdown.metadata)?;
+        let mut content = parsed_markdown.content.clone();
+        if !external.is_empty() {
+            let page = serde_json::to_value(&parsed_markdown.metadata)
+                .context("Couldn't serialize front-matter")?;
+            let outer = serde_json::to_value(&self.config).context("Couldn't serialize config")?;
+            for preprocessor in external {
+                content = preprocessor.run(&Input {
+                    page: &page,
+                    content: &content,
+                    path: &path.to_string_lossy(),
+                    outer: &outer,
+                })?;
+            }
+        }
+
+        // Formulas are rendered before the built-in preprocessors run, so they can't touch them
+        let (binding, formulas) = match self.config.misc.latex {
+            Some(true) => math::extract(&content)
+                .with_context(|| format!("Couldn't render the math in `{}`", path.display()))?,
+            _ => (content, Formulas::default()),
+        };
+        let url = page_url(path);
+        let root = self.root(&url);
+
+        // `{{page}}` is the front-matter, and the page's table of contents once it's rendered
+        let mut page = serde_json::to_value(&parsed_markdown.metadata)
+            .context("Couldn't serialize front-matter")?;
+        if let Some(styles) = &parsed_markdown.metadata.additional_css {
+            page["additional_css"] = json!(styles
+                .iter()
+                .map(|style| match is_external(style) || style.starts_with('/') {
+                    true => style.clone(),
+                    false => format!("{root}{style}"),
+                })
+                .collect::<Vec<String>>());
+        }
+
+        let mut context = json!({"page": &page, "outer": &self.config});
+        // Parameters are only known when the page is requested, so `{{id}}` is left for the web-server to render
+        for param in parsed_markdown.metadata.params.iter().flatten() {
+            if context.get(&param.name).is_none() {
+                context[&param.name] = json!(format!("{{{{{}}}}}", param.name));
+            }
+        }
+
+        let (mut html_output, toc) = markdown::to_html(
+            &binding,
+            |link| self.page_link(&url, link),
+            self.highlighter.as_ref(),
+            |text, previous| {
+                preprocessors
+                    .iter()
+                    .fold(text.to_string(), |text, preprocessor| {
+                        preprocessor.run(&text, previous)
+                    })
+            },
+            // The heading is rendered with the page, an error will be reported then. Formulas are shown as their LaTeX.
+            |text| {
+                let text = self
+                    .reg
+                    .render_template(text, &context)
+                    .unwrap_or_else(|_| text.to_string());
+                formulas.restore_source(&text)
+            },
+        );
+        page["toc"] = json!(toc);
+        context["page"]["toc"] = json!(toc);
+
         // =======================================
 
         // * Render in-markdown templates (the user can use handlebars even from the files)
 
-        html_output = reg
-            .render_template(
-                &html_output,
-                &json!({"page": &parsed_markdown.metadata, "outer": &config}),
-            )
+        html_output = self
+            .reg
+            .render_template(&html_output, &context)
             .context("Couldn't render unregistered template")?;
+        html_output = formulas.restore(&html_output);
 
         // =======================================
 
         // * Render using page's configuration ===
 
-        f.write_if_different(
-            reg.render(
-                "page_template",
-                &json!({
-                "content": html_output,
-                "sidebar": summary,
-                "page": &parsed_markdown.metadata,
-                    "misc": &config.misc
-                }),
-            )
-            .with_context(|| {
-                format!(
-                    "Couldn't render template for page `{}`",
-                    path.file_name().to_string_lossy()
+        let html_path = self.html_path(path);
+        if let Some(parent) = html_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Couldn't create directory `{}`", parent.display()))?;
+        }
+
+        let (prev, next) = self.summary.neighbours(&url);
+        let link = |neighbour: Link| Link {
+            url: self.link(&url, &neighbour.url),
+            ..neighbour
+        };
+        let mut sidebar = self.summary.for_page(&url);
+        sidebar.rewrite_urls(|chapter| self.link(&url, chapter));
+
+        html_path.write_if_different(
+            self.reg
+                .render(
+                    "page_template",
+                    &json!({
+                    "content": html_output,
+                    "sidebar": sidebar,
+                    "current_url": url,
+                    "root": root,
+                    "page": &page,
+                    "prev": prev.map(link),
+                    "next": next.map(link),
+                        "misc": &self.config.misc
+                    }),
                 )
-            })?
-            .as_bytes(),
-            format!(
-                "{}/static/{}.html",
-                outdir.display(),
-                &filename_str[..filename_str.len() - 3]
-            ),
+                .with_context(|| {
+                    format!(
+                        "Couldn't render template for page `{}`",
+                        path.file_name().unwrap_or_default().to_string_lossy()
+                    )
+                })?
+                .as_bytes(),
         )?;
         // =======================================
 
         // Throw an error if an unknown property is found
         {
-            let params_in_page = params_in_path(path.path());
+            let params_in_page = params_in_path(path);
             if let Some(params) = &parsed_markdown.metadata.params {
                 for param in params {
                     if !params_in_page.contains(&param.name) {
@@ -398,128 +1122,679 @@ fn build(port: u16, outdir: &Path, sassbin: String) -> Result<()> {
             }
         }
 
-        pages.push(Page {
+        let headings: Vec<String> = toc.into_iter().map(|heading| heading.title).collect();
+
+        Ok(Page {
+            search: search::Entry::new(
+                &parsed_markdown.metadata.title,
+                url,
+                &html_output,
+                &headings,
+            ),
             config: parsed_markdown.metadata,
-            path: path.path().to_string_lossy().to_string(),
-        });
+            path: path.to_string_lossy().to_string(),
+        })
     }
 
-    f.write_if_different(
-		reg.render(
-			"rocket_routing_template",
-			&json!({
-				"port": port,
-				"directory": canonicalize(outdir).context("Couldn't canonicalize output directory")?.join("static"),
-				"pages": pages,
-				"config_path": CONFIG_PATH.to_string_lossy()
-			}),
-		).context("Couldn't render `src/main.rs`")?
-		.as_bytes(),
-		cargo_project.join("src").join("main.rs"))
-	.with_context(|| {
-		format!(
-			"Couldn't create | open file {}",
-			cargo_project.join("src").with_file_name("main.rs").display()
-		)
-	})?;
-
-    let mut f = File::create(cargo_project.join("Rocket.toml")).with_context(|| {
-        format!(
-            "Couldn't create | open file {}",
-            cargo_project.join("Rocket.toml").display()
-        )
-    })?;
-
-    f.write_if_different(
-        reg.render(
-            "rocket_toml",
-            &json!({
-				"config_path": CONFIG_PATH.to_string_lossy()
-			}),
+    /// Writes the search index of every page, unless it's disabled with `search = false`
+    fn write_search_index(&self) -> Result<()> {
+        if self.config.misc.search == Some(false) {
+            return Ok(());
+        }
+
+        // Urls in the index are relative to the root of the site
+        let static_entries: Vec<search::Entry>;
+        let mut entries: Vec<&search::Entry> = match self.target {
+            Target::Server => self.pages.iter().map(|page| &page.search).collect(),
+            Target::Static => {
+                static_entries = self
+                    .pages
+                    .iter()
+                    .map(|page| search::Entry {
+                        url: self.site_link(&page.search.url),
+                        ..page.search.clone()
+                    })
+                    .collect();
+                static_entries.iter().collect()
+            }
+        };
+        self.site_dir().join(search::INDEX_FILE).write_if_different(
+            search::index(&mut entries)
+                .context("Couldn't serialize search index")?
+                .as_bytes(),
         )
-        .context("Couldn't render Rocket.toml template (id: `rocket_toml`)")?
-        .as_bytes(),
-        cargo_project.join("Rocket.toml"),
-    )?;
+    }
 
-    // ===========================================
+    /// Writes the web-server that serves the pages, in the language of the selected backend.
+    fn write_routing(&self) -> Result<()> {
+        if self.target == Target::Server {
+            self.remove_other_backends()?;
+        }
+        match (self.target, self.config.routing.backend) {
+            (Target::Static, _) => Ok(()),
+            (Target::Server, Backend::Rocket) => self.write_rocket_routing(),
+            (Target::Server, Backend::Axum) => self.write_axum_routing(),
+            (Target::Server, Backend::Go) => self.write_go_routing(),
+        }
+    }
 
-    // * Compile styles ==========================
+    /// Removes the files the other backends generate in `<outdir>/routing`, so switching `backend` doesn't leave a stale web-server that can still be run.
+    fn remove_other_backends(&self) -> Result<()> {
+        let routing = self.outdir.join("routing");
+        let stale: &[&str] = match self.config.routing.backend {
+            Backend::Rocket => &["main.go"],
+            Backend::Axum => &["main.go", "Rocket.toml"],
+            Backend::Go => &["Cargo.toml", "Cargo.lock", "Rocket.toml", "src/main.rs"],
+        };
 
-    if Path::new("src/styles").exists() {
-        compile_styles(
-            &format!("{}/static/styles", &outdir.display()),
-            #[cfg(feature = "sass")]
-            &sassbin,
-        )?;
+        for file in stale {
+            let path = routing.join(file);
+            if path.is_file() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Couldn't remove `{}`", path.display()))?;
+            }
+        }
+        // Fails if it isn't empty (or doesn't exist), then it's left as it is
+        if self.config.routing.backend == Backend::Go {
+            let _ = fs::remove_dir(routing.join("src"));
+        }
+        Ok(())
     }
 
-    if !Path::new(&format!("{}/static/styles", outdir.display())).exists() {
-        fs::create_dir(&format!("{}/static/styles", outdir.display())).with_context(|| {
-            format!(
-                "Couldn't create directory `{}/static/styles`",
-                outdir.display()
+    /// Writes the reverse-proxy configurations selected with `proxies` in `[routing]`.
+    fn write_proxies(&self) -> Result<()> {
+        if self.config.routing.proxies.is_empty() {
+            return Ok(());
+        }
+        let Some(proxy_dir) = self.proxy_dir() else {
+            eprintln!("Warning: the output directory of `--target static` is published, so the reverse-proxy configurations are only written with `proxy_dir` in `[routing]`");
+            return Ok(());
+        };
+        if !proxy_dir.exists() {
+            fs::create_dir_all(&proxy_dir)
+                .with_context(|| format!("Couldn't create directory `{}`", proxy_dir.display()))?;
+        }
+
+        for proxy in &self.config.routing.proxies {
+            let (template, file) = match proxy {
+                Proxy::Nginx => ("nginx_conf", "nginx.conf"),
+                Proxy::Caddy => ("caddyfile", "Caddyfile"),
+            };
+
+            proxy_dir.join(file).write_if_different(
+                self.reg
+                    .render(
+                        template,
+                        &json!({
+                            "port": self.port,
+                            "directory": canonicalize(self.site_dir()).context("Couldn't canonicalize output directory")?,
+                        }),
+                    )
+                    .with_context(|| format!("Couldn't render `{file}`"))?
+                    .as_bytes(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the Axum project that serves the pages.
+    fn write_axum_routing(&self) -> Result<()> {
+        let cargo_project = self.outdir.join("routing");
+
+        cargo_project
+            .join("Cargo.toml")
+            .write_if_different(self.templates.read("routing-axum/Cargo.toml")?.as_bytes())
+            .context("Couldn't write `routing/Cargo.toml`")?;
+
+        cargo_project
+            .join("src")
+            .join("main.rs")
+            .write_if_different(
+                self.reg
+                    .render(
+                        "axum_routing_template",
+                        &json!({
+                            "port": self.port,
+                            "directory": canonicalize(self.outdir).context("Couldn't canonicalize output directory")?.join("static"),
+                            "pages": self.pages,
+                        }),
+                    )
+                    .context("Couldn't render `routing-axum/src/main.rs.hbs`")?
+                    .as_bytes(),
             )
-        })?;
+            .context("Couldn't write `routing/src/main.rs`")
     }
 
-    // * Copy built-in styles ====================
+    /// Writes the Go server that serves the pages (`<outdir>/routing/main.go`).
+    fn write_go_routing(&self) -> Result<()> {
+        let routing = &self.config.routing;
+        self.outdir
+            .join("routing")
+            .join("main.go")
+            .write_if_different(
+                self.reg
+                    .render(
+                        "go_routing",
+                        &json!({
+                            "port": self.port,
+                            "directory": canonicalize(self.outdir).context("Couldn't canonicalize output directory")?.join("static"),
+                            "imports": routing.imports,
+                            "init_behaviour": routing.init_behaviour.as_deref().unwrap_or_default(),
+                            "fail_behaviour": routing.fail_behaviour.as_deref().unwrap_or("panic(err)"),
+                        }),
+                    )
+                    .context("Couldn't render `routing.go.hbs`")?
+                    .as_bytes(),
+            )
+            .context("Couldn't write `routing/main.go`")
+    }
+
+    /// Writes the Rocket project that serves the pages.
+    fn write_rocket_routing(&self) -> Result<()> {
+        let outdir = self.outdir;
+        let cargo_project = outdir.join("routing");
 
-    for file in read_dir(CONFIG_PATH.join("templates").join("styles"))
+        cargo_project
+            .join("Cargo.toml")
+            .write_if_different(self.templates.read("routing/Cargo.toml")?.as_bytes())
+            .context("Couldn't write to routing file")?;
+
+        cargo_project.join("src").join("main.rs").write_if_different(
+            self.reg.render(
+                "rocket_routing_template",
+                &json!({
+                    "port": self.port,
+                    "directory": canonicalize(outdir).context("Couldn't canonicalize output directory")?.join("static"),
+                    "pages": self.pages,
+                    "config_path": CONFIG_PATH.to_string_lossy()
+                }),
+            ).context("Couldn't render `src/main.rs`")?
+            .as_bytes())
         .with_context(|| {
             format!(
-                "Couldn't get directory {}",
-                CONFIG_PATH.join("templates").join("styles").display()
+                "Couldn't create | open file {}",
+                cargo_project.join("src").with_file_name("main.rs").display()
             )
-        })?
-        .filter_map(|e| e.ok())
-    {
-        std::fs::copy(
-            file.path(),
-            format!(
-                "{}/static/styles/{}",
-                outdir.display(),
-                file.file_name().to_string_lossy()
-            ),
+        })?;
+
+        cargo_project.join("Rocket.toml").write_if_different(
+            self.reg
+                .render(
+                    "rocket_toml",
+                    &json!({
+                        "config_path": CONFIG_PATH.to_string_lossy()
+                    }),
+                )
+                .context("Couldn't render Rocket.toml template (id: `rocket_toml`)")?
+                .as_bytes(),
         )
-        .with_context(|| {
+    }
+
+    fn styles(&self) -> Result<()> {
+        let styles_dir = self.site_dir().join("styles");
+
+        // * Compile styles ==========================
+
+        if Path::new("src/styles").exists() {
+            compile_styles(
+                &styles_dir.to_string_lossy(),
+                #[cfg(feature = "sass")]
+                &self.sassbin,
+            )?;
+        }
+
+        if !styles_dir.exists() {
+            fs::create_dir(&styles_dir)
+                .with_context(|| format!("Couldn't create directory `{}`", styles_dir.display()))?;
+        }
+
+        // * Copy built-in styles ====================
+
+        for (name, content) in self.templates.styles()? {
+            styles_dir
+                .join(&name)
+                .write_if_different(&content)
+                .with_context(|| {
+                    format!(
+                        "Couldn't copy built-in style `{name}` to `{}`",
+                        styles_dir.display()
+                    )
+                })?;
+        }
+
+        // * Syntax highlighting theme ===============
+
+        if let Some(highlighter) = &self.highlighter {
+            styles_dir
+                .join(highlight::STYLESHEET)
+                .write_if_different(highlighter.css().as_bytes())
+                .with_context(|| {
+                    format!(
+                        "Couldn't write the syntax highlighting theme to `{}`",
+                        styles_dir.display()
+                    )
+                })?;
+        }
+
+        // ===========================================
+
+        Ok(())
+    }
+}
+
+/// Builds the preprocessors enabled in `[preprocessors]`.
+fn preprocessors(config: &Config) -> Result<Preprocessors> {
+    cuteness::preprocessors(
+        &config.preprocessors,
+        config.misc.html_lang.as_deref().unwrap_or("en"),
+    )
+    .context("Couldn't load the preprocessors (`[preprocessors]` in `cuteconfig.toml`)")
+}
+
+/// Loads the syntax highlighter if `syntax_highlighting` is enabled.
+fn highlighter(config: &Config) -> Result<Option<Highlighter>> {
+    if config.misc.syntax_highlighting != Some(true) {
+        return Ok(None);
+    }
+    Highlighter::new(&config.highlighting)
+        .map(Some)
+        .context("Couldn't load the syntax highlighter (`[highlighting]` in `cuteconfig.toml`)")
+}
+
+/// Registers every template, partial and helper.
+fn registry(templates: &Templates) -> Result<Handlebars<'static>> {
+    // * Register all templates and helpers ======
+
+    let mut reg = Handlebars::new();
+    reg.register_escape_fn(no_escape);
+    reg.register_template_string("page_template", templates.read("page.html.hbs")?)
+        .context("Couldn't register page.html.hbs")?;
+    reg.register_template_string(
+        "rocket_routing_template",
+        templates.read("routing/src/main.rs.hbs")?,
+    )
+    .context("Couldn't register `templates/routing/src/main.rs.hbs`")?;
+
+    reg.register_template_string("rocket_toml", templates.read("routing/Rocket.toml.hbs")?)
+        .context("Couldn't register Rocket.toml.hbs")?;
+
+    reg.register_template_string(
+        "axum_routing_template",
+        templates.read("routing-axum/src/main.rs.hbs")?,
+    )
+    .context("Couldn't register `templates/routing-axum/src/main.rs.hbs`")?;
+
+    reg.register_template_string("go_routing", templates.read("routing.go.hbs")?)
+        .context("Couldn't register routing.go.hbs")?;
+
+    reg.register_template_string("nginx_conf", templates.read("proxy/nginx.conf.hbs")?)
+        .context("Couldn't register proxy/nginx.conf.hbs")?;
+
+    reg.register_template_string("caddyfile", templates.read("proxy/Caddyfile.hbs")?)
+        .context("Couldn't register proxy/Caddyfile.hbs")?;
+
+    for (name, partial) in templates.partials()? {
+        reg.register_partial(&name, partial)
+            .with_context(|| format!("Couldn't register partial `{name}`"))?;
+    }
+
+    handlebars_helper!(lower: |method: String| method.to_lowercase());
+    reg.register_helper("lower", Box::new(lower));
+
+    handlebars_helper!(file_name: |path: String| {
+        let name = Path::new(&path).file_name().unwrap().to_str().unwrap();
+        &name[..name.len() - 3]
+    });
+
+    reg.register_helper("file_name", Box::new(file_name));
+
+    handlebars_helper!(sanitize: |path: String| {
+
+        lazy_static!{
+            static ref RE: regex::Regex = regex::Regex::new("([<>])").unwrap();
+        };
+
+        RE.replace_all(&path, "_").to_string()
+    });
+    reg.register_helper("sanitize", Box::new(sanitize));
+
+    // `user/<id>` is `user/:id` for Axum
+    handlebars_helper!(axum_route: |path: String| {
+        lazy_static!{
+            static ref RE: regex::Regex = regex::Regex::new("<([^<>]*)>").unwrap();
+        };
+
+        RE.replace_all(&path, ":$1").to_string()
+    });
+    reg.register_helper("axum_route", Box::new(axum_route));
+
+    handlebars_helper!(contains: |src: String, search: String| { src.contains(&search)});
+    reg.register_helper("contains", Box::new(contains));
+
+    handlebars_helper!(is_pure: |src: String| {
+        lazy_static!{
+            static ref RE: regex::Regex = regex::Regex::new("([<>])").unwrap();
+        };
+
+        !RE.is_match(&src)
+    });
+    reg.register_helper("is_pure", Box::new(is_pure));
+
+    handlebars_helper!(cut_end: |src: String, to_cut: usize| {
+        &src[..src.len() - to_cut]
+    });
+
+    reg.register_helper("cut_end", Box::new(cut_end));
+
+    handlebars_helper!(cut_start: |src: String, to_cut: usize| {
+        &src[to_cut..]
+    });
+
+    reg.register_helper("cut_start", Box::new(cut_start));
+
+    // ===========================================
+
+    Ok(reg)
+}
+
+/// Reads `cuteconfig.toml` and `SUMMARY.toml`
+fn read_configuration() -> Result<(Config, SummaryConfig)> {
+    Ok((read_config()?, read_summary()?))
+}
+
+fn read_config() -> Result<Config> {
+    // * Read configuration ========================
+
+    let mut content = String::new();
+    if !Path::new("cuteconfig.toml").exists() {
+        bail!("Couldn't find cuteconfig.toml");
+    }
+
+    let mut f = File::open("cuteconfig.toml").context("Couldn't open `cuteconfig.toml`")?;
+    f.read_to_string(&mut content)
+        .context("Couldn't read configuration `cuteconfig.toml`")?;
+
+    toml::from_str::<Config>(&content).context("Couldn't parse configuration")
+}
+
+fn read_summary() -> Result<SummaryConfig> {
+    // * Generate sidebar from SUMMARY.toml
+
+    if !Path::new("SUMMARY.toml").exists() {
+        bail!("Couldn't find SUMMARY.toml");
+    }
+
+    let mut summary: SummaryConfig = toml::from_str(
+        &read_to_string("SUMMARY.toml").context("Couldn't get file `SUMMARY.toml`")?,
+    )
+    .context("Couldn't parse summary in `SUMMARY.toml`")?;
+    summary
+        .prepare()
+        .context("Invalid summary in `SUMMARY.toml`")?;
+
+    Ok(summary)
+}
+
+/// Keeps rebuilding whatever changes. `on_rebuild` is called after every successful rebuild.
+fn watch(site: &mut Site, mut on_rebuild: impl FnMut()) -> Result<()> {
+    println!("Watching for changes...");
+
+    cuteness::watch::watch(|changed| {
+        for path in changed {
+            println!("Changed: {}", path.display());
+        }
+
+        // A broken page shouldn't stop watching, the user will fix it and save again.
+        match site.rebuild(changed) {
+            Ok(()) => on_rebuild(),
+            Err(e) => eprintln!("Error: {e:?}"),
+        }
+    })
+}
+
+fn serve(port: u16, outdir: &Path, sassbin: String) -> Result<()> {
+    let mut site = Site::new(port, outdir, sassbin, Target::Server, UrlStyle::default())?;
+    site.build()?;
+
+    let reloader = cuteness::serve::Reloader::default();
+    cuteness::serve::serve(
+        canonicalize(outdir)
+            .context("Couldn't canonicalize output directory")?
+            .join("static"),
+        port,
+        reloader.clone(),
+    )?;
+
+    watch(&mut site, || reloader.reload())
+}
+
+/// Results of `cuteness doctor`
+#[derive(Default)]
+struct Report {
+    failures: usize,
+    warnings: usize,
+}
+
+impl Report {
+    fn pass(&mut self, check: &str, detail: impl std::fmt::Display) {
+        println!("[ ok ] {check}: {detail}");
+    }
+
+    /// Something that doesn't stop the build, but probably isn't what the user wants
+    fn warn(&mut self, check: &str, detail: impl std::fmt::Display, hint: &str) {
+        self.warnings += 1;
+        println!("[warn] {check}: {detail}\n       hint: {hint}");
+    }
+
+    fn fail(&mut self, check: &str, detail: impl std::fmt::Display, hint: &str) {
+        self.failures += 1;
+        println!("[FAIL] {check}: {detail}\n       hint: {hint}");
+    }
+}
+
+/// Whether `program --version` runs successfully
+fn is_installed(program: &str) -> bool {
+    std::process::Command::new(program)
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Checks everything `build` needs, printing a report with hints to fix every problem. Fails if any check failed.
+fn doctor(outdir: &Path, sassbin: &str) -> Result<()> {
+    let mut report = Report::default();
+
+    // * Configuration directory and templates ====
+
+    let installed = CONFIG_PATH.join("templates");
+    if installed.is_dir() {
+        report.pass("Configuration directory", CONFIG_PATH.display());
+        let missing: Vec<&str> = templates::DEFAULT_TEMPLATES
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| !installed.join(name).is_file())
+            .collect();
+        if missing.is_empty() {
+            report.pass("Installed templates", "every template is installed");
+        } else {
+            report.warn(
+                "Installed templates",
+                format!(
+                    "`{}` missing from `{}`, the built-in copies are used instead",
+                    missing.join("`, `"),
+                    installed.display()
+                ),
+                "run `cuteness update` to install the missing templates",
+            );
+        }
+    } else {
+        report.warn(
+            "Configuration directory",
             format!(
-                "Couldn't copy file `{}` to `{}/static/styles/{}`",
-                file.path().display(),
-                outdir.display(),
-                file.file_name().to_string_lossy()
-            )
-        })?;
+                "`{}` doesn't exist, the built-in templates are used",
+                installed.display()
+            ),
+            "run `cuteness setup` (or `cuteness setup --from <path>` without network access)",
+        );
+    }
+
+    // * Tools =====================================
+
+    if is_installed("git") {
+        report.pass("git", "found");
+    } else {
+        report.warn(
+            "git",
+            "`git` isn't on PATH, it's needed by `setup`, `update` and `theme add`",
+            "install git, or use `cuteness setup --from <path>`",
+        );
+    }
+
+    if cfg!(feature = "sass") {
+        if is_installed(sassbin) {
+            report.pass("Sass", format!("`{sassbin}` found"));
+        } else if Path::new("src/styles").exists() {
+            report.fail(
+                "Sass",
+                format!("`{sassbin}` isn't on PATH, it's needed to compile `src/styles`"),
+                "install Sass (https://sass-lang.com/install), or pass its path with `--sassbin`",
+            );
+        } else {
+            report.warn(
+                "Sass",
+                format!("`{sassbin}` isn't on PATH, it will be needed if you add `src/styles`"),
+                "install Sass (https://sass-lang.com/install), or pass its path with `--sassbin`",
+            );
+        }
+    }
+
+    // * Project ===================================
+
+    let config = match read_config() {
+        Ok(config) => {
+            report.pass("cuteconfig.toml", "parsed");
+            Some(config)
+        }
+        Err(e) => {
+            report.fail(
+                "cuteconfig.toml",
+                format!("{e:#}"),
+                "fix the file, or run `cuteness init` in an empty directory to see a working one",
+            );
+            None
+        }
+    };
+
+    match read_summary() {
+        Ok(_) => report.pass("SUMMARY.toml", "parsed"),
+        Err(e) => report.fail(
+            "SUMMARY.toml",
+            format!("{e:#}"),
+            "every entry needs exactly one of `title` (with an `url`), `part` or `separator`",
+        ),
+    }
+
+    if let Some(config) = &config {
+        match Templates::new(config.misc.theme.as_deref())
+            .and_then(|templates| registry(&templates))
+        {
+            Ok(_) => report.pass("Templates", "every template compiles"),
+            Err(e) => report.fail(
+                "Templates",
+                format!("{e:#}"),
+                "fix the template, or install the theme with `cuteness theme add`",
+            ),
+        }
+
+        match lock_mismatch(config) {
+            Ok(None) if Path::new(LOCK_FILE).exists() => {
+                report.pass("Template version", format!("matches `{LOCK_FILE}`"))
+            }
+            Ok(None) => report.pass("Template version", format!("not pinned (no `{LOCK_FILE}`)")),
+            Ok(Some(problem)) => report.warn(
+                "Template version",
+                &problem,
+                "builds on other machines may use different templates",
+            ),
+            Err(e) => report.fail(
+                "Template version",
+                format!("{e:#}"),
+                &format!("delete `{LOCK_FILE}` and run `cuteness update` to create it again"),
+            ),
+        }
+    }
+
+    // * Output directory ==========================
+
+    // The output directory is created by `build`, so what needs to be writable is the closest existing directory
+    let writable = outdir
+        .ancestors()
+        .map(|dir| {
+            if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            }
+        })
+        .find(|dir| dir.exists())
+        .map(|dir| {
+            let probe = dir.join(".cuteness-doctor");
+            let result = fs::write(&probe, b"");
+            let _ = fs::remove_file(&probe);
+            result
+        });
+    match writable {
+        Some(Ok(())) => report.pass(
+            "Output directory",
+            format!("`{}` is writable", outdir.display()),
+        ),
+        Some(Err(e)) => report.fail(
+            "Output directory",
+            format!("`{}` isn't writable: {e}", outdir.display()),
+            "check its permissions, or choose another one with `--outdir`",
+        ),
+        None => report.fail(
+            "Output directory",
+            format!("`{}` can't be created", outdir.display()),
+            "choose another one with `--outdir`",
+        ),
     }
 
     // ===========================================
 
+    println!();
+    if report.failures > 0 {
+        bail!(
+            "{} check(s) failed, {} warning(s)",
+            report.failures,
+            report.warnings
+        );
+    }
+    println!("Everything looks fine! ({} warning(s))", report.warnings);
     Ok(())
 }
 
 /// Write to file ONLY if the contents are different
 trait WriteIfDifferent {
-    /// Writes
-    fn write_if_different<P: AsRef<Path>>(&mut self, buf: &[u8], path: P) -> Result<()>;
+    /// Writes `buf` to this path, leaving the file untouched (and its modification time) if it already has those contents.
+    fn write_if_different(&self, buf: &[u8]) -> Result<()>;
 }
 
-impl<W> WriteIfDifferent for W
+impl<P> WriteIfDifferent for P
 where
-    W: Write,
+    P: AsRef<Path>,
 {
-    fn write_if_different<P: AsRef<Path>>(&mut self, buf: &[u8], path: P) -> Result<()> {
-        // Check hashes
-
-        if !(path.as_ref().exists()
-            && blake3::hash(buf)
-                == blake3::hash(
-                    fs::read_to_string(path)
-                        .context("Couldn't read path")?
-                        .as_bytes(),
-                ))
-        {
-            self.write_all(buf).context("Couldn't write to file")?;
+    fn write_if_different(&self, buf: &[u8]) -> Result<()> {
+        let path = self.as_ref();
+
+        // Check hashes before opening the file for writing, as that would truncate it
+
+        if let Ok(current) = fs::read(path) {
+            if blake3::hash(buf) == blake3::hash(&current) {
+                return Ok(());
+            }
         }
-        Ok(())
+
+        fs::write(path, buf).with_context(|| format!("Couldn't write to file `{}`", path.display()))
     }
 }
@@ -0,0 +1,359 @@
+//! Markdown to HTML conversion.
+
+use hashbrown::HashSet;
+use lazy_static::lazy_static;
+use pulldown_cmark::{
+    escape::escape_html, html, CodeBlockKind, Event, HeadingLevel, LinkType, Options, Parser, Tag,
+};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::highlight::Highlighter;
+
+lazy_static! {
+    /// Handlebars expressions and URLs, which prose transforms shouldn't touch
+    static ref PROTECTED: Regex = Regex::new(r#"\{\{.*?\}\}\}?|[A-Za-z][A-Za-z0-9+.-]*://[^\s"]*"#).unwrap();
+}
+
+/// A heading in the page, used for the page's table of contents (`page.toc`).
+#[derive(Serialize, Clone, Debug)]
+pub struct Heading {
+    /// 1 for `<h1>`, 2 for `<h2>`...
+    pub level: u32,
+    /// Anchor of the heading, link to it with `#{{id}}`
+    pub id: String,
+    /// Text of the heading, HTML-escaped
+    pub title: String,
+}
+
+/// Converts Markdown to HTML, giving every heading an unique `id` (unless it already has one, e.g. `# Title {#my-id}`). Every link's destination is replaced with `link(destination)`, code blocks are highlighted with `highlighter` if there's one, and the prose is passed through `prose` (see [`transform_prose`]). The text of every heading, Handlebars expressions included, is passed through `heading` (e.g. to render them) before it's used for the heading's `id` and title. Returns the HTML and the page's headings in order.
+pub fn to_html(
+    markdown: &str,
+    link: impl Fn(&str) -> String,
+    highlighter: Option<&Highlighter>,
+    prose: impl Fn(&str, Option<char>) -> String,
+    heading: impl Fn(&str) -> String,
+) -> (String, Vec<Heading>) {
+    let mut events = Vec::new();
+    let mut toc = Vec::new();
+    // Index of the heading's placeholder event, and the heading's text so far
+    let mut current_heading: Option<(usize, String)> = None;
+    // The info string (e.g. `rust {3}`) and the code of the code block being highlighted
+    let mut current_code: Option<(String, String)> = None;
+
+    // Quotes are left to the `prose` transform, as smart punctuation would also change them inside Handlebars expressions
+    let options = Options::all() - Options::ENABLE_SMART_PUNCTUATION;
+
+    // Ids given with `{#id}` are reserved first, so a generated one can't take them, even before them
+    let mut used_ids = HashSet::new();
+    for event in Parser::new_ext(markdown, options) {
+        if let Event::Start(Tag::Heading(_, Some(id), _)) = event {
+            if !used_ids.insert(id.to_string()) {
+                eprintln!("Warning: more than one heading has the id `{id}`");
+            }
+        }
+    }
+    for event in transform_prose(Parser::new_ext(markdown, options), prose) {
+        match event {
+            Event::Start(Tag::Heading(..)) => {
+                // Replaced by the opening tag once we know the heading's text
+                current_heading = Some((events.len(), String::new()));
+                events.push(event);
+            }
+            Event::End(Tag::Heading(level, id, classes)) => {
+                let Some((start, text)) = current_heading.take() else {
+                    continue;
+                };
+                let text = heading(&text);
+
+                let id = match id {
+                    Some(id) => id.to_string(),
+                    None => unique_id(slugify(&text), &used_ids),
+                };
+                used_ids.insert(id.clone());
+
+                let level = heading_level(level);
+                let mut open = format!("<h{level} id=\"");
+                escape_html(&mut open, &id).unwrap();
+                open.push('"');
+                if !classes.is_empty() {
+                    open.push_str(" class=\"");
+                    escape_html(&mut open, &classes.join(" ")).unwrap();
+                    open.push('"');
+                }
+                open.push('>');
+
+                events[start] = Event::Html(open.into());
+                events.push(Event::Html(format!("</h{level}>\n").into()));
+
+                let mut title = String::new();
+                escape_html(&mut title, &text).unwrap();
+                toc.push(Heading { level, id, title });
+            }
+            Event::Start(Tag::Link(kind, destination, title))
+                if !matches!(kind, LinkType::Autolink | LinkType::Email) =>
+            {
+                events.push(Event::Start(Tag::Link(
+                    kind,
+                    link(&destination).into(),
+                    title,
+                )));
+            }
+            Event::Start(Tag::CodeBlock(kind)) if highlighter.is_some() => {
+                let info = match kind {
+                    CodeBlockKind::Fenced(info) => info.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                current_code = Some((info, String::new()));
+            }
+            Event::Text(ref text) if current_code.is_some() => {
+                if let Some((_, code)) = &mut current_code {
+                    code.push_str(text);
+                }
+            }
+            Event::End(Tag::CodeBlock(_)) if current_code.is_some() => {
+                if let (Some((info, code)), Some(highlighter)) = (current_code.take(), highlighter)
+                {
+                    events.push(Event::Html(highlighter.highlight(&code, &info).into()));
+                }
+            }
+            Event::Text(ref text) | Event::Code(ref text) => {
+                if let Some((_, heading_text)) = &mut current_heading {
+                    heading_text.push_str(text);
+                }
+                events.push(event);
+            }
+            // A Handlebars expression, which `transform_prose` leaves as HTML
+            Event::Html(ref html) if html.starts_with("{{") => {
+                if let Some((_, heading_text)) = &mut current_heading {
+                    heading_text.push_str(html);
+                }
+                events.push(event);
+            }
+            _ => events.push(event),
+        }
+    }
+
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, events.into_iter());
+    (html_output, toc)
+}
+
+/// Applies `transform` to every text outside code blocks and autolinks. Consecutive texts are merged first, so a transform can see a whole sentence even if Markdown split it (e.g. at a `_`); Handlebars expressions (`{{...}}`) and URLs in it are left out, and Handlebars expressions aren't HTML-escaped. `transform` also gets the character before each piece of text in its paragraph, if any.
+///
+/// Code spans, inline HTML and everything else that isn't a text event is never transformed.
+fn transform_prose<'a>(
+    events: impl Iterator<Item = Event<'a>>,
+    transform: impl Fn(&str, Option<char>) -> String,
+) -> Vec<Event<'a>> {
+    let mut transformed = Vec::new();
+    // Text waiting for the next non-text event
+    let mut pending = String::new();
+    // Last character in the current paragraph (or heading, list item...)
+    let mut previous: Option<char> = None;
+    // How many code blocks and autolinks we are in
+    let mut verbatim = 0;
+
+    for event in events {
+        if let Event::Text(text) = &event {
+            if verbatim == 0 {
+                pending.push_str(text);
+                continue;
+            }
+        }
+
+        if !pending.is_empty() {
+            let mut text = String::with_capacity(pending.len());
+            let mut last = 0;
+            for protected in PROTECTED.find_iter(&pending) {
+                let before = pending[..last].chars().last().or(previous);
+                text.push_str(&transform(&pending[last..protected.start()], before));
+                if protected.as_str().starts_with("{{") {
+                    // Written as it is, or the quotes of its string literals would be escaped
+                    transformed.push(Event::Text(std::mem::take(&mut text).into()));
+                    transformed.push(Event::Html(protected.as_str().to_string().into()));
+                } else {
+                    text.push_str(protected.as_str());
+                }
+                last = protected.end();
+            }
+            let before = pending[..last].chars().last().or(previous);
+            text.push_str(&transform(&pending[last..], before));
+
+            previous = pending.chars().last();
+            transformed.push(Event::Text(text.into()));
+            pending.clear();
+        }
+
+        match &event {
+            Event::Start(Tag::CodeBlock(_))
+            | Event::Start(Tag::Link(LinkType::Autolink | LinkType::Email, ..)) => verbatim += 1,
+            Event::End(Tag::CodeBlock(_))
+            | Event::End(Tag::Link(LinkType::Autolink | LinkType::Email, ..)) => verbatim -= 1,
+            // Inline elements continue the paragraph, anything else starts a new one
+            Event::Start(
+                Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) | Tag::Image(..),
+            ) => {}
+            Event::Start(_) => previous = None,
+            Event::Code(code) | Event::Html(code) => previous = code.chars().last(),
+            Event::SoftBreak | Event::HardBreak => previous = Some('\n'),
+            _ => {}
+        }
+        transformed.push(event);
+    }
+    transformed
+}
+
+/// Converts a heading's text to an anchor: "Hello, World!" is `hello-world`.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    for c in text.trim().chars() {
+        if c.is_alphanumeric() || c == '_' {
+            slug.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || c == '-') && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Adds `-1`, `-2`... to `id` until it's not in `used`.
+fn unique_id(id: String, used: &HashSet<String>) -> String {
+    if !used.contains(&id) {
+        return id;
+    }
+    (1..)
+        .map(|n| format!("{id}-{n}"))
+        .find(|candidate| !used.contains(candidate))
+        .unwrap()
+}
+
+#[inline]
+fn heading_level(level: HeadingLevel) -> u32 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math;
+
+    /// Converts `markdown` with `prose` and without any other change.
+    fn html(markdown: &str, prose: impl Fn(&str, Option<char>) -> String) -> String {
+        to_html(markdown, str::to_string, None, prose, str::to_string).0
+    }
+
+    fn upper(text: &str, _previous: Option<char>) -> String {
+        text.to_uppercase()
+    }
+
+    #[test]
+    fn prose_is_transformed() {
+        assert_eq!(
+            html("Some *emphasis* and `code`", upper),
+            "<p>SOME <em>EMPHASIS</em> AND <code>code</code></p>\n"
+        );
+        assert_eq!(
+            html("```\ncode\n```\n<b>html</b> and <https://x.org>", upper),
+            "<pre><code>code\n</code></pre>\n<p><b>HTML</b> AND <a href=\"https://x.org\">https://x.org</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn protected_spans() {
+        assert_eq!(
+            html(r#"a {{page.title}} b {{{raw "x"}}} c"#, upper),
+            "<p>A {{page.title}} B {{{raw \"x\"}}} C</p>\n"
+        );
+        assert_eq!(
+            html("see https://example.com/a_b, ok", upper),
+            "<p>SEE https://example.com/a_b, OK</p>\n"
+        );
+    }
+
+    #[test]
+    fn previous_character() {
+        let previous = |text: &str, previous: Option<char>| format!("[{previous:?}]{text}");
+        assert_eq!(
+            html("a *b* `c`d", previous),
+            "<p>[None]a <em>[Some(' ')]b</em>[Some('b')] <code>c</code>[Some('c')]d</p>\n"
+        );
+    }
+
+    #[test]
+    fn headings() {
+        let (html, toc) = to_html(
+            "# Hello, World!\n## Hello, World!\n## Custom {#custom}\n## A & B",
+            str::to_string,
+            None,
+            |text, _| text.to_string(),
+            str::to_string,
+        );
+        assert!(html.starts_with("<h1 id=\"hello-world\">Hello, World!</h1>"));
+        let ids: Vec<&str> = toc.iter().map(|heading| heading.id.as_str()).collect();
+        assert_eq!(ids, ["hello-world", "hello-world-1", "custom", "a-b"]);
+        assert_eq!(toc[3].title, "A &amp; B");
+        assert_eq!(toc[1].level, 2);
+    }
+
+    #[test]
+    fn explicit_ids_are_reserved() {
+        let (_, toc) = to_html(
+            "## Intro\n## Other {#intro}\n## Intro",
+            str::to_string,
+            None,
+            |text, _| text.to_string(),
+            str::to_string,
+        );
+        let ids: Vec<&str> = toc.iter().map(|heading| heading.id.as_str()).collect();
+        assert_eq!(ids, ["intro-1", "intro", "intro-2"]);
+    }
+
+    #[test]
+    fn heading_with_handlebars() {
+        let (html, toc) = to_html(
+            "# {{page.title}}",
+            str::to_string,
+            None,
+            |text, _| text.to_string(),
+            |text| text.replace("{{page.title}}", "My Title"),
+        );
+        assert_eq!(html, "<h1 id=\"my-title\">{{page.title}}</h1>\n");
+        assert_eq!(toc[0].title, "My Title");
+    }
+
+    #[test]
+    fn heading_with_math() {
+        let (markdown, formulas) = math::extract("## Energy $E=mc^2$").unwrap();
+        let (_, toc) = to_html(
+            &markdown,
+            str::to_string,
+            None,
+            |text, _| text.to_string(),
+            |text| formulas.restore_source(text),
+        );
+        assert_eq!(toc[0].id, "energy-emc2");
+        assert_eq!(toc[0].title, "Energy E=mc^2");
+    }
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  a -- b  "), "a-b");
+        assert_eq!(slugify("Ünïcode_text"), "ünïcode_text");
+        assert_eq!(slugify("!!!"), "section");
+    }
+}
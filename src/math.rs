@@ -0,0 +1,261 @@
+//! Server-side math: `$...$` and `$$...$$` are rendered to MathML while building, so pages don't need any JavaScript.
+
+use std::{fmt::Write, ops::Range};
+
+use anyhow::{anyhow, Result};
+use latex2mathml::{latex_to_mathml, DisplayStyle};
+use pulldown_cmark::{Event, Options, Parser, Tag};
+
+// Formulas are replaced by `OPEN<index>CLOSE` until the page is rendered. Private use characters aren't touched by the preprocessors, Markdown or Handlebars.
+const OPEN: char = '\u{E000}';
+const CLOSE: char = '\u{E001}';
+
+struct Formula {
+    latex: String,
+    mathml: String,
+}
+
+/// The formulas taken out of a page by [`extract`].
+#[derive(Default)]
+pub struct Formulas(Vec<Formula>);
+
+/// Renders every formula in `markdown` (except in code and HTML), replacing it with a placeholder. Returns the Markdown with placeholders and the rendered formulas, to put them back with [`Formulas::restore`] once the page is rendered.
+///
+/// Like in Pandoc, an inline formula can't start or end with a space and its closing `$` can't be followed by a digit, so prices like `$5 or $10` are left as they are. A `$` can also be escaped with `\$`.
+pub fn extract(markdown: &str) -> Result<(String, Formulas)> {
+    let mut ranges: Vec<Range<usize>> = Parser::new_ext(markdown, Options::all())
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) | Event::Html(_) => Some(range),
+            _ => None,
+        })
+        .collect();
+    ranges.sort_by_key(|range| range.start);
+    let mut excluded = Excluded {
+        ranges: &ranges,
+        next: 0,
+    };
+
+    let bytes = markdown.as_bytes();
+    let mut output = String::with_capacity(markdown.len());
+    let mut formulas = Vec::new();
+    // Everything before this has already been copied to `output`
+    let mut copied = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if excluded.contains(i) {
+            i += 1;
+            continue;
+        }
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'$' => {
+                let display = bytes.get(i + 1) == Some(&b'$');
+                let delimiter = if display { 2 } else { 1 };
+                let start = i + delimiter;
+                // A copy, as we go back to `start` if the formula isn't closed
+                let Some(end) = closing(markdown, start, display, excluded) else {
+                    i = start;
+                    continue;
+                };
+
+                let latex = &markdown[start..end];
+                let style = match display {
+                    true => DisplayStyle::Block,
+                    false => DisplayStyle::Inline,
+                };
+                let mathml = latex_to_mathml(latex.trim(), style)
+                    .map_err(|e| anyhow!("Couldn't render formula `{}`: {e}", latex.trim()))?;
+
+                output.push_str(&markdown[copied..i]);
+                write!(output, "{OPEN}{}{CLOSE}", formulas.len()).unwrap();
+                formulas.push(Formula {
+                    latex: latex.trim().to_string(),
+                    mathml,
+                });
+                i = end + delimiter;
+                copied = i;
+            }
+            _ => i += 1,
+        }
+    }
+
+    output.push_str(&markdown[copied..]);
+    Ok((output, Formulas(formulas)))
+}
+
+/// The ranges of the Markdown that can't contain formulas (code and HTML), sorted by their start.
+#[derive(Clone, Copy)]
+struct Excluded<'a> {
+    ranges: &'a [Range<usize>],
+    /// The first range that doesn't end before the last position checked
+    next: usize,
+}
+
+impl Excluded<'_> {
+    /// Whether `i` is in one of the ranges. Positions have to be checked in order, so every range is only skipped once.
+    fn contains(&mut self, i: usize) -> bool {
+        while self
+            .ranges
+            .get(self.next)
+            .is_some_and(|range| range.end <= i)
+        {
+            self.next += 1;
+        }
+        self.ranges
+            .get(self.next)
+            .is_some_and(|range| range.contains(&i))
+    }
+}
+
+/// Position of the `$` (or `$$`) closing the formula that starts at `start`, if it's closed before a blank line.
+fn closing(markdown: &str, start: usize, display: bool, mut excluded: Excluded) -> Option<usize> {
+    let bytes = markdown.as_bytes();
+    if !display && bytes.get(start).is_none_or(u8::is_ascii_whitespace) {
+        return None;
+    }
+
+    let mut i = start;
+    while i < bytes.len() {
+        if excluded.contains(i) {
+            return None;
+        }
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\n'
+                if markdown[i + 1..]
+                    .trim_start_matches([' ', '\t'])
+                    .starts_with('\n') =>
+            {
+                return None
+            }
+            b'$' if display => {
+                if bytes.get(i + 1) == Some(&b'$') {
+                    return (i > start).then_some(i);
+                }
+                i += 1;
+            }
+            b'$' => {
+                let closes = !bytes[i - 1].is_ascii_whitespace()
+                    && !bytes.get(i + 1).is_some_and(u8::is_ascii_digit);
+                return closes.then_some(i);
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+impl Formulas {
+    /// Replaces the placeholders in the rendered `html` with the formulas' MathML.
+    pub fn restore(&self, html: &str) -> String {
+        self.replace(html, |formula, output| output.push_str(&formula.mathml))
+    }
+
+    /// Replaces the placeholders in `text` (e.g. a heading's text, to build its anchor and title) with the formulas' LaTeX.
+    pub fn restore_source(&self, text: &str) -> String {
+        self.replace(text, |formula, output| output.push_str(&formula.latex))
+    }
+
+    fn replace(&self, text: &str, mut write: impl FnMut(&Formula, &mut String)) -> String {
+        if self.0.is_empty() {
+            return text.to_string();
+        }
+
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(OPEN) {
+            output.push_str(&rest[..start]);
+            let after = &rest[start + OPEN.len_utf8()..];
+            let formula = after.split_once(CLOSE).and_then(|(index, after)| {
+                Some((self.0.get(index.parse::<usize>().ok()?)?, after))
+            });
+            match formula {
+                Some((formula, after)) => {
+                    write(formula, &mut output);
+                    rest = after;
+                }
+                // Not one of our placeholders, keep it as it is
+                None => {
+                    output.push(OPEN);
+                    rest = after;
+                }
+            }
+        }
+        output.push_str(rest);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The LaTeX of the formulas in `markdown`, and the Markdown with `[i]` instead of the placeholders.
+    fn formulas(markdown: &str) -> (String, Vec<String>) {
+        let (output, formulas) = extract(markdown).unwrap();
+        let output = output.replace(OPEN, "[").replace(CLOSE, "]");
+        (output, formulas.0.into_iter().map(|f| f.latex).collect())
+    }
+
+    #[test]
+    fn inline_and_display() {
+        assert_eq!(
+            formulas("Energy $E=mc^2$ and $$ \\sum x $$."),
+            (
+                "Energy [0] and [1].".to_string(),
+                vec!["E=mc^2".to_string(), "\\sum x".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn display_delimiters() {
+        // `$` inside a display formula doesn't close it
+        assert_eq!(formulas("$$a$b$$").1, vec!["a$b"]);
+        assert_eq!(formulas("$$$$").1, Vec::<String>::new());
+        assert_eq!(formulas("$$a$").1, Vec::<String>::new());
+    }
+
+    #[test]
+    fn prices_are_not_formulas() {
+        assert_eq!(formulas("$5 or $10").1, Vec::<String>::new());
+        assert_eq!(formulas("from $ 5 to $ 10").1, Vec::<String>::new());
+        assert_eq!(formulas("$x $").1, Vec::<String>::new());
+    }
+
+    #[test]
+    fn escaped_dollars() {
+        assert_eq!(formulas(r"\$x$ costs \$5").1, Vec::<String>::new());
+        assert_eq!(formulas(r"$a \$ b$").1, vec![r"a \$ b"]);
+    }
+
+    #[test]
+    fn blank_line_ends_formula() {
+        assert_eq!(formulas("$a\n\nb$").1, Vec::<String>::new());
+        assert_eq!(formulas("$a\nb$").1, vec!["a\nb"]);
+    }
+
+    #[test]
+    fn code_and_html_are_left_out() {
+        assert_eq!(
+            formulas("`$x$` $y$\n\n```\n$z$\n```\n\n<div>$w$</div>\n"),
+            (
+                "`$x$` [0]\n\n```\n$z$\n```\n\n<div>$w$</div>\n".to_string(),
+                vec!["y".to_string()]
+            )
+        );
+        // A formula can't end in code
+        assert_eq!(formulas("$a `b$`").1, Vec::<String>::new());
+    }
+
+    #[test]
+    fn restore() {
+        let (output, formulas) = extract("$a<b$").unwrap();
+        assert_eq!(formulas.restore_source(&output), "a<b");
+        assert!(formulas.restore(&output).starts_with("<math"));
+        // Not a placeholder
+        assert_eq!(formulas.restore_source("\u{E000}x"), "\u{E000}x");
+    }
+}
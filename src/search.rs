@@ -0,0 +1,144 @@
+//! The full-text search index, written to `<outdir>/static/search-index.json` and used by the search box in the page template.
+
+use hashbrown::HashSet;
+use serde::{Deserialize, Serialize};
+
+/// Name of the index file, in the output's `static` directory.
+pub const INDEX_FILE: &str = "search-index.json";
+
+/// Words shorter than this aren't indexed
+const MIN_TOKEN_LENGTH: usize = 2;
+
+/// A page in the search index.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Entry {
+    pub title: String,
+    pub url: String,
+    pub headings: Vec<String>,
+    /// Every distinct word in the page, lowercased, in order of appearance
+    pub tokens: Vec<String>,
+}
+
+impl Entry {
+    /// Creates the entry for a page from its rendered HTML content and headings.
+    pub fn new(title: &str, url: String, html: &str, headings: &[String]) -> Self {
+        Entry {
+            title: title.to_string(),
+            url,
+            headings: headings
+                .iter()
+                .map(|heading| extract_text(heading).trim().to_string())
+                .collect(),
+            tokens: tokenize(&extract_text(html)),
+        }
+    }
+}
+
+/// Serializes the index. `entries` are sorted by url so the index doesn't change between builds unless a page does.
+pub fn index(entries: &mut [&Entry]) -> serde_json::Result<String> {
+    entries.sort_by(|a, b| a.url.cmp(&b.url));
+    serde_json::to_string(&serde_json::json!({ "pages": entries }))
+}
+
+/// Removes HTML tags (and the contents of `<script>` and `<style>`) and decodes the basic entities.
+pub fn extract_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let tag_end = rest.find('>').map_or(rest.len(), |end| end + 1);
+        let tag = rest[..tag_end].to_ascii_lowercase();
+        rest = &rest[tag_end..];
+
+        for skipped in ["script", "style"] {
+            if tag.starts_with(&format!("<{skipped}")) {
+                let closing = format!("</{skipped}>");
+                rest = rest
+                    .to_ascii_lowercase()
+                    .find(&closing)
+                    .map_or("", |end| &rest[end + closing.len()..]);
+            }
+        }
+
+        // Tags separate words (`<li>a</li><li>b</li>` is "a b")
+        text.push(' ');
+    }
+    text.push_str(rest);
+
+    decode_entities(&text)
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Splits `text` into distinct lowercase words.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= MIN_TOKEN_LENGTH)
+        .map(str::to_lowercase)
+        .filter(|word| seen.insert(word.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracting_text() {
+        assert_eq!(
+            extract_text("<p>Hello <em>world</em></p>")
+                .split_whitespace()
+                .collect::<Vec<_>>(),
+            ["Hello", "world"]
+        );
+        assert_eq!(extract_text("<li>a</li><li>b</li>").trim(), "a  b");
+        assert_eq!(
+            extract_text("a &lt;b&gt; &amp;lt; &quot;c&quot;"),
+            "a <b> &lt; \"c\""
+        );
+    }
+
+    #[test]
+    fn scripts_and_styles_are_skipped() {
+        assert_eq!(
+            extract_text("a<script>let x = 1;</script>b<STYLE>p {}</STYLE>c").replace(' ', ""),
+            "abc"
+        );
+        assert_eq!(extract_text("a<script>unclosed").trim(), "a");
+        assert_eq!(extract_text("a <unclosed").trim(), "a");
+    }
+
+    #[test]
+    fn tokenizing() {
+        assert_eq!(
+            tokenize("Hello, hello WORLD! A café-au-lait x2"),
+            ["hello", "world", "café", "au", "lait", "x2"]
+        );
+        assert!(tokenize("a b c !").is_empty());
+    }
+
+    #[test]
+    fn index_is_sorted() {
+        let b = Entry::new("B", "b".to_string(), "<p>Bee</p>", &[]);
+        let a = Entry::new(
+            "A",
+            "a".to_string(),
+            "<p>Ant</p>",
+            &["<code>Intro</code>".to_string()],
+        );
+        let index = index(&mut [&b, &a]).unwrap();
+        assert!(index.find("\"url\":\"a\"").unwrap() < index.find("\"url\":\"b\"").unwrap());
+        assert!(index.contains("\"headings\":[\"Intro\"]"));
+    }
+}
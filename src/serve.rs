@@ -0,0 +1,276 @@
+//! A small development web-server used by `cuteness serve`. It serves the output's `static` directory the same way the generated router does and tells the open browser tabs to reload after every rebuild.
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+
+/// Path of the Server-Sent Events endpoint the injected script listens to.
+const RELOAD_ENDPOINT: &str = "/__cuteness/reload";
+
+/// Script injected before `</body>` in every served HTML page.
+const RELOAD_SCRIPT: &str = r#"<script>new EventSource("/__cuteness/reload").onmessage = () => location.reload();</script>"#;
+
+/// How often an idle reload connection gets a keep-alive comment, so closed tabs are noticed.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Handle used to notify the open browser tabs that the site has been rebuilt.
+#[derive(Clone, Default)]
+pub struct Reloader {
+    generation: Arc<(Mutex<u64>, Condvar)>,
+}
+
+impl Reloader {
+    /// Tells every connected browser tab to reload.
+    pub fn reload(&self) {
+        let (lock, cvar) = &*self.generation;
+        *lock.lock().unwrap() += 1;
+        cvar.notify_all();
+    }
+
+    /// Blocks until the generation changes from `seen` or `timeout` passes. Returns the current generation and whether it changed.
+    fn wait(&self, seen: u64, timeout: Duration) -> (u64, bool) {
+        let (lock, cvar) = &*self.generation;
+        let (generation, _) = cvar
+            .wait_timeout_while(lock.lock().unwrap(), timeout, |generation| {
+                *generation == seen
+            })
+            .unwrap();
+        (*generation, *generation != seen)
+    }
+
+    fn current(&self) -> u64 {
+        *self.generation.0.lock().unwrap()
+    }
+}
+
+/// Starts serving `root` at `port` in a background thread.
+pub fn serve(root: PathBuf, port: u16, reloader: Reloader) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .with_context(|| format!("Couldn't bind to port {port}"))?;
+
+    println!("Serving `{}` at http://localhost:{port}/", root.display());
+
+    thread::spawn(move || {
+        for stream in listener.incoming().filter_map(|e| e.ok()) {
+            let root = root.clone();
+            let reloader = reloader.clone();
+            thread::spawn(move || {
+                // A browser closing the connection isn't an error worth reporting.
+                let _ = handle(stream, &root, &reloader);
+            });
+        }
+    });
+
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream, root: &Path, reloader: &Reloader) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // Skip the headers, we don't need them
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return respond(&mut stream, "400 Bad Request", "text/plain", b"Bad request"),
+    };
+
+    if method != "GET" && method != "HEAD" {
+        return respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"Method not allowed",
+        );
+    }
+
+    let url = percent_decode(target.split(['?', '#']).next().unwrap_or("/"));
+
+    if url == RELOAD_ENDPOINT {
+        return reload_events(stream, reloader);
+    }
+
+    match resolve(root, &url) {
+        Some(path) => {
+            let mut body = fs::read(&path)?;
+            let mime = mime_type(&path);
+            if mime == "text/html" {
+                body = inject_reload_script(body);
+            }
+            if method == "HEAD" {
+                body.clear();
+            }
+            respond(&mut stream, "200 OK", mime, &body)
+        }
+        None => match fs::read(root.join("404.html")) {
+            Ok(body) => respond(
+                &mut stream,
+                "404 Not Found",
+                "text/html",
+                &inject_reload_script(body),
+            ),
+            Err(_) => respond(&mut stream, "404 Not Found", "text/plain", b"Not found"),
+        },
+    }
+}
+
+/// Maps an URL to a file in `root`, following the same rules as the generated router: `/introduction` is `introduction.html` and directories serve their `index.html`.
+fn resolve(root: &Path, url: &str) -> Option<PathBuf> {
+    let relative = Path::new(url.trim_start_matches('/'));
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let mut path = root.join(relative);
+    if path.is_dir() {
+        path.push("index.html");
+    } else if !path.exists() && path.extension().is_none() {
+        path.set_extension("html");
+    }
+
+    path.is_file().then_some(path)
+}
+
+/// Keeps the connection open, sending an event each time the reloader fires.
+fn reload_events(mut stream: TcpStream, reloader: &Reloader) -> Result<()> {
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n",
+    )?;
+
+    let mut seen = reloader.current();
+    loop {
+        let (generation, reloaded) = reloader.wait(seen, KEEP_ALIVE);
+        seen = generation;
+        if reloaded {
+            stream.write_all(b"data: reload\n\n")?;
+        } else {
+            stream.write_all(b": keep-alive\n\n")?;
+        }
+        stream.flush()?;
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, mime: &str, body: &[u8]) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {mime}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn inject_reload_script(mut html: Vec<u8>) -> Vec<u8> {
+    let position = html
+        .windows(b"</body>".len())
+        .rposition(|window| window.eq_ignore_ascii_case(b"</body>"))
+        .unwrap_or(html.len());
+    html.splice(position..position, RELOAD_SCRIPT.bytes());
+    html
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .as_deref()
+    {
+        Some("html") => "text/html",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("json") | Some("map") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("txt") => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Decodes `%XX` escapes, browsers send `user<id>` as `user%3Cid%3E`.
+fn percent_decode(url: &str) -> String {
+    let bytes = url.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                result.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        result.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("/caf%C3%A9"), "/café");
+        assert_eq!(percent_decode("/a%20b"), "/a b");
+        assert_eq!(percent_decode("/100%"), "/100%");
+        assert_eq!(percent_decode("/%zz"), "/%zz");
+    }
+
+    #[test]
+    fn resolving() {
+        let root = std::env::temp_dir().join(format!("cuteness-serve-{}", std::process::id()));
+        fs::create_dir_all(root.join("guide")).unwrap();
+        for file in [
+            "index.html",
+            "introduction.html",
+            "guide/intro.html",
+            "guide/index.html",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        assert_eq!(resolve(&root, "/"), Some(root.join("index.html")));
+        assert_eq!(
+            resolve(&root, "/introduction"),
+            Some(root.join("introduction.html"))
+        );
+        assert_eq!(
+            resolve(&root, "/guide/intro"),
+            Some(root.join("guide/intro.html"))
+        );
+        assert_eq!(
+            resolve(&root, "/guide"),
+            Some(root.join("guide/index.html"))
+        );
+        assert_eq!(resolve(&root, "/missing"), None);
+        assert_eq!(resolve(&root, "/../etc/passwd"), None);
+        assert_eq!(resolve(&root, "/guide/../introduction"), None);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,500 @@
+//! `SUMMARY.toml`, the table of contents shown in the sidebar.
+
+use anyhow::{bail, Result};
+use hashbrown::HashSet;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SummaryConfig {
+    /// Number the chapters ("1", "1.2", "1.2.3"...)
+    #[serde(default)]
+    pub numbered: bool,
+    pub map: Vec<Map>,
+}
+
+/// An entry in the summary. It's either a chapter (`title` and `url`, with optional nested `children`), a part header (`part`) or a separator (`separator = true`).
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Map {
+    pub title: Option<String>,
+    pub url: Option<String>,
+    /// Sections of this chapter
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Map>,
+    /// Title of the part header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub separator: bool,
+    /// `numbered = false` leaves this chapter (and its sections) without a number, useful for prefaces and appendices
+    #[serde(default = "default_true")]
+    pub numbered: bool,
+    /// Section number, computed from the position in the summary
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub number: Option<String>,
+    /// Whether this is the page being rendered
+    #[serde(default, skip_deserializing)]
+    pub is_current: bool,
+    /// Whether the page being rendered is one of this chapter's sections
+    #[serde(default, skip_deserializing)]
+    pub is_ancestor: bool,
+}
+
+#[inline(always)]
+fn default_true() -> bool {
+    true
+}
+
+impl SummaryConfig {
+    /// Checks that every entry is exactly one of chapter, part header or separator, and computes the section numbers.
+    pub fn prepare(&mut self) -> Result<()> {
+        validate(&self.map, true)?;
+        if self.numbered {
+            number(&mut self.map, "");
+        }
+        Ok(())
+    }
+}
+
+/// A link to another page, given to the page template as `prev` and `next`.
+#[derive(Serialize)]
+pub struct Link {
+    pub title: String,
+    pub url: String,
+}
+
+impl Map {
+    #[inline]
+    pub fn is_chapter(&self) -> bool {
+        self.part.is_none() && !self.separator
+    }
+}
+
+fn validate(entries: &[Map], top_level: bool) -> Result<()> {
+    for entry in entries {
+        let kinds = [entry.title.is_some(), entry.part.is_some(), entry.separator];
+        if kinds.iter().filter(|kind| **kind).count() != 1 {
+            bail!(
+                "Summary entries must have exactly one of `title`, `part` or `separator` (found {})",
+                describe(entry)
+            );
+        }
+
+        if entry.is_chapter() && entry.url.is_none() {
+            bail!("Chapter {} doesn't have an `url`", describe(entry));
+        }
+
+        if !entry.is_chapter() {
+            if entry.url.is_some() || !entry.children.is_empty() {
+                bail!(
+                    "Part headers and separators can't have an `url` or `children` (found {})",
+                    describe(entry)
+                );
+            }
+            if !top_level {
+                bail!(
+                    "Part headers and separators can only be used at the top level (found {})",
+                    describe(entry)
+                );
+            }
+        }
+
+        validate(&entry.children, false)?;
+    }
+    Ok(())
+}
+
+fn number(entries: &mut [Map], prefix: &str) {
+    let mut counter = 0;
+    for entry in entries
+        .iter_mut()
+        .filter(|entry| entry.is_chapter() && entry.numbered)
+    {
+        counter += 1;
+        let section = format!("{prefix}{counter}");
+        number(&mut entry.children, &format!("{section}."));
+        entry.number = Some(section);
+    }
+}
+
+fn describe(entry: &Map) -> String {
+    match (&entry.title, &entry.part, &entry.url) {
+        (Some(title), _, _) => format!("`{title}`"),
+        (None, Some(part), _) => format!("part `{part}`"),
+        (None, None, Some(url)) => format!("an entry with url `{url}`"),
+        _ => "a separator".to_string(),
+    }
+}
+
+/// How to report a problem found while validating the summary.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Fail the build
+    Error,
+    /// Print a warning and continue
+    #[default]
+    Warning,
+    Ignore,
+}
+
+/// `[validation]` in `cuteconfig.toml`
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct ValidationConfig {
+    /// Summary entries linking to a page that doesn't exist
+    #[serde(default)]
+    pub dangling_entries: Severity,
+    /// Pages that aren't linked from the summary
+    #[serde(default)]
+    pub orphan_pages: Severity,
+}
+
+impl SummaryConfig {
+    /// Every chapter and section, in reading order
+    pub fn chapters(&self) -> Vec<&Map> {
+        fn collect<'a>(entries: &'a [Map], chapters: &mut Vec<&'a Map>) {
+            for entry in entries.iter().filter(|entry| entry.is_chapter()) {
+                chapters.push(entry);
+                collect(&entry.children, chapters);
+            }
+        }
+
+        let mut chapters = Vec::new();
+        collect(&self.map, &mut chapters);
+        chapters
+    }
+
+    /// Every chapter's url, in reading order
+    pub fn urls(&self) -> Vec<&str> {
+        self.chapters()
+            .into_iter()
+            .filter_map(|chapter| chapter.url.as_deref())
+            .collect()
+    }
+
+    /// A copy of the summary with `is_current` and `is_ancestor` set for the page at `url`.
+    pub fn for_page(&self, url: &str) -> SummaryConfig {
+        fn mark(entries: &mut [Map], url: &str) -> bool {
+            let mut found = false;
+            for entry in entries.iter_mut().filter(|entry| entry.is_chapter()) {
+                entry.is_current = entry.url.as_deref().map(normalize_url).as_deref() == Some(url);
+                entry.is_ancestor = mark(&mut entry.children, url);
+                found |= entry.is_current || entry.is_ancestor;
+            }
+            found
+        }
+
+        let mut summary = self.clone();
+        mark(&mut summary.map, url);
+        summary
+    }
+
+    /// Replaces every chapter's `url` with `link(url)`.
+    pub fn rewrite_urls(&mut self, link: impl Fn(&str) -> String) {
+        fn rewrite(entries: &mut [Map], link: &impl Fn(&str) -> String) {
+            for entry in entries {
+                if let Some(url) = &mut entry.url {
+                    *url = link(url);
+                }
+                rewrite(&mut entry.children, link);
+            }
+        }
+
+        rewrite(&mut self.map, &link);
+    }
+
+    /// The chapters before and after the page at `url` in reading order, skipping links to other sites.
+    pub fn neighbours(&self, url: &str) -> (Option<Link>, Option<Link>) {
+        let chapters: Vec<&Map> = self
+            .chapters()
+            .into_iter()
+            .filter(|chapter| chapter.url.as_deref().is_some_and(|url| !is_external(url)))
+            .collect();
+
+        let Some(position) = chapters
+            .iter()
+            .position(|chapter| chapter.url.as_deref().map(normalize_url).as_deref() == Some(url))
+        else {
+            return (None, None);
+        };
+
+        let link = |chapter: &&Map| Link {
+            title: chapter.title.clone().unwrap_or_default(),
+            url: chapter.url.clone().unwrap_or_default(),
+        };
+
+        (
+            position
+                .checked_sub(1)
+                .and_then(|i| chapters.get(i))
+                .map(link),
+            chapters.get(position + 1).map(link),
+        )
+    }
+
+    /// Cross-checks the summary with `pages` (pairs of url and source path), printing warnings and returning an error for every problem configured as such.
+    pub fn validate_pages(
+        &self,
+        pages: &[(String, String)],
+        config: &ValidationConfig,
+    ) -> Result<()> {
+        let mut errors = Vec::new();
+        let mut report = |severity: Severity, message: String| match severity {
+            Severity::Error => errors.push(message),
+            Severity::Warning => eprintln!("Warning: {message}"),
+            Severity::Ignore => {}
+        };
+
+        let urls: Vec<String> = self
+            .urls()
+            .into_iter()
+            .filter(|url| !is_external(url))
+            .map(normalize_url)
+            .collect();
+        let linked: HashSet<&str> = urls.iter().map(String::as_str).collect();
+        let existing: HashSet<&str> = pages.iter().map(|(url, _)| url.as_str()).collect();
+
+        for url in &urls {
+            if !existing.contains(url.as_str()) {
+                report(
+                    config.dangling_entries,
+                    format!("`SUMMARY.toml` links to `{url}`, but there's no page `src/{url}.md`"),
+                );
+            }
+        }
+
+        for (url, path) in pages {
+            if !linked.contains(url.as_str()) {
+                report(
+                    config.orphan_pages,
+                    format!("Page `{path}` isn't linked from `SUMMARY.toml`"),
+                );
+            }
+        }
+
+        if !errors.is_empty() {
+            bail!("{}", errors.join("\n"));
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+pub fn is_external(url: &str) -> bool {
+    url.contains("://") || url.starts_with("mailto:")
+}
+
+/// Resolves `path`, relative to the directory `dir` of the site (e.g. `guide` for the page `guide/intro`), to a path from the root of the site: `../introduction` from `guide` is `introduction`. `None` if it goes above the root.
+pub fn resolve_url(dir: &str, path: &str) -> Option<String> {
+    let mut segments: Vec<&str> = dir.split('/').filter(|s| !s.is_empty()).collect();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+    Some(segments.join("/"))
+}
+
+/// `/introduction.html#usage` is the same page as `introduction`
+pub fn normalize_url(url: &str) -> String {
+    let url = url.split(['#', '?']).next().unwrap_or_default();
+    let url = url.trim_start_matches("./").trim_matches('/');
+    url.strip_suffix(".html").unwrap_or(url).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUMMARY: &str = r#"
+        numbered = true
+
+        [[map]]
+        title = "Introduction"
+        url = "introduction"
+        numbered = false
+
+        [[map]]
+        part = "Guide"
+
+        [[map]]
+        title = "Guide"
+        url = "guide"
+            [[map.children]]
+            title = "Install"
+            url = "guide#install"
+            [[map.children]]
+            title = "Usage"
+            url = "guide/usage"
+                [[map.children.children]]
+                title = "Advanced"
+                url = "guide/advanced.html"
+
+        [[map]]
+        separator = true
+
+        [[map]]
+        title = "Website"
+        url = "https://example.com"
+
+        [[map]]
+        title = "Appendix"
+        url = "/appendix"
+    "#;
+
+    fn example() -> SummaryConfig {
+        let mut summary: SummaryConfig = toml::from_str(SUMMARY).unwrap();
+        summary.prepare().unwrap();
+        summary
+    }
+
+    #[test]
+    fn numbering() {
+        let summary = example();
+        let guide = &summary.map[2];
+        assert_eq!(summary.map[0].number, None);
+        assert_eq!(guide.number.as_deref(), Some("1"));
+        assert_eq!(guide.children[1].number.as_deref(), Some("1.2"));
+        assert_eq!(
+            guide.children[1].children[0].number.as_deref(),
+            Some("1.2.1")
+        );
+        assert_eq!(summary.map[5].number.as_deref(), Some("3"));
+        assert_eq!(summary.map[1].number, None);
+    }
+
+    #[test]
+    fn invalid_entries() {
+        let prepare = |summary: &str| toml::from_str::<SummaryConfig>(summary).unwrap().prepare();
+        assert!(prepare("[[map]]\ntitle = \"No url\"").is_err());
+        assert!(prepare("[[map]]\ntitle = \"A\"\npart = \"B\"\nurl = \"a\"").is_err());
+        assert!(prepare("[[map]]\npart = \"Part\"\nurl = \"a\"").is_err());
+        assert!(
+            prepare("[[map]]\nseparator = true\n[[map.children]]\ntitle = \"A\"\nurl = \"a\"")
+                .is_err()
+        );
+        assert!(prepare(
+            "[[map]]\ntitle = \"A\"\nurl = \"a\"\n[[map.children]]\npart = \"Nested\""
+        )
+        .is_err());
+        assert!(prepare("[[map]]\ntitle = \"A\"\nurl = \"a\"").is_ok());
+    }
+
+    fn pages(urls: &[&str]) -> Vec<(String, String)> {
+        urls.iter()
+            .map(|url| (url.to_string(), format!("src/{url}.md")))
+            .collect()
+    }
+
+    #[test]
+    fn validating_pages() {
+        let summary = example();
+        let strict = ValidationConfig {
+            dangling_entries: Severity::Error,
+            orphan_pages: Severity::Error,
+        };
+        let all = [
+            "introduction",
+            "guide",
+            "guide/usage",
+            "guide/advanced",
+            "appendix",
+        ];
+        assert!(summary.validate_pages(&pages(&all), &strict).is_ok());
+
+        // `appendix` is missing and `extra` isn't linked
+        let error = summary
+            .validate_pages(&pages(&all[..4]), &strict)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("`appendix`"));
+        let error = summary
+            .validate_pages(&pages(&[&all[..], &["extra"]].concat()), &strict)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("`src/extra.md`"));
+    }
+
+    #[test]
+    fn ignored_problems() {
+        let config = ValidationConfig {
+            dangling_entries: Severity::Ignore,
+            orphan_pages: Severity::Warning,
+        };
+        assert!(example()
+            .validate_pages(&pages(&["extra"]), &config)
+            .is_ok());
+    }
+
+    #[test]
+    fn neighbour_pages() {
+        let summary = example();
+        let urls = |(prev, next): (Option<Link>, Option<Link>)| {
+            (prev.map(|link| link.url), next.map(|link| link.url))
+        };
+
+        assert_eq!(
+            urls(summary.neighbours("introduction")),
+            (None, Some("guide".to_string()))
+        );
+        assert_eq!(
+            urls(summary.neighbours("guide/usage")),
+            (
+                Some("guide#install".to_string()),
+                Some("guide/advanced.html".to_string())
+            )
+        );
+        // Links to other sites are skipped
+        assert_eq!(
+            urls(summary.neighbours("appendix")),
+            (Some("guide/advanced.html".to_string()), None)
+        );
+        assert_eq!(urls(summary.neighbours("missing")), (None, None));
+    }
+
+    #[test]
+    fn current_page_and_ancestors() {
+        let summary = example().for_page("guide/advanced");
+        let guide = &summary.map[2];
+        let usage = &guide.children[1];
+        assert!(guide.is_ancestor && !guide.is_current);
+        assert!(usage.is_ancestor && !usage.is_current);
+        assert!(usage.children[0].is_current);
+        assert!(!guide.children[0].is_current && !summary.map[0].is_current);
+
+        // A section linking to its own chapter's page is both
+        let summary = example().for_page("guide");
+        let guide = &summary.map[2];
+        assert!(guide.is_current && guide.is_ancestor);
+        assert!(guide.children[0].is_current);
+    }
+
+    #[test]
+    fn resolving_urls() {
+        assert_eq!(
+            resolve_url("guide", "../introduction").as_deref(),
+            Some("introduction")
+        );
+        assert_eq!(resolve_url("guide", "test").as_deref(), Some("guide/test"));
+        assert_eq!(resolve_url("guide", "./a/../b").as_deref(), Some("guide/b"));
+        assert_eq!(
+            resolve_url("", "img/cat.png").as_deref(),
+            Some("img/cat.png")
+        );
+        assert_eq!(resolve_url("guide/", "sub/").as_deref(), Some("guide/sub"));
+        assert_eq!(resolve_url("guide", "."), Some("guide".to_string()));
+        assert_eq!(resolve_url("", ".."), None);
+        assert_eq!(resolve_url("guide", "../../introduction"), None);
+    }
+
+    #[test]
+    fn normalizing_urls() {
+        assert_eq!(normalize_url("/introduction.html#usage"), "introduction");
+        assert_eq!(normalize_url("./guide/intro?x=1"), "guide/intro");
+        assert_eq!(normalize_url("guide/"), "guide");
+        assert_eq!(normalize_url("#top"), "");
+    }
+}
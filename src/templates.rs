@@ -0,0 +1,178 @@
+//! Templates and built-in styles. Each file is looked up in this order, so any single one can be overridden:
+//!
+//! 1. The project's `templates` directory.
+//! 2. The theme selected with `theme` in `cuteconfig.toml` (`CONFIG_PATH/themes/<name>`).
+//! 3. `CONFIG_PATH/templates` (installed with `cuteness setup`).
+//! 4. The copies compiled into the binary, so cuteness can build without network access or `git`.
+
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use hashbrown::HashMap;
+use walkdir::WalkDir;
+
+use crate::CONFIG_PATH;
+
+/// Every template shipped with cuteness, by its path relative to the `templates` directory.
+pub const DEFAULT_TEMPLATES: [(&str, &str); 10] = [
+    ("page.html.hbs", include_str!("../templates/page.html.hbs")),
+    (
+        "routing/src/main.rs.hbs",
+        include_str!("../templates/routing/src/main.rs.hbs"),
+    ),
+    (
+        "routing/Cargo.toml",
+        include_str!("../templates/routing/Cargo.toml"),
+    ),
+    (
+        "routing/Rocket.toml.hbs",
+        include_str!("../templates/routing/Rocket.toml.hbs"),
+    ),
+    (
+        "routing-axum/src/main.rs.hbs",
+        include_str!("../templates/routing-axum/src/main.rs.hbs"),
+    ),
+    (
+        "routing-axum/Cargo.toml",
+        include_str!("../templates/routing-axum/Cargo.toml"),
+    ),
+    (
+        "routing.go.hbs",
+        include_str!("../templates/routing.go.hbs"),
+    ),
+    (
+        "proxy/nginx.conf.hbs",
+        include_str!("../templates/proxy/nginx.conf.hbs"),
+    ),
+    (
+        "proxy/Caddyfile.hbs",
+        include_str!("../templates/proxy/Caddyfile.hbs"),
+    ),
+    (
+        "styles/index.css",
+        include_str!("../templates/styles/index.css"),
+    ),
+];
+
+/// The project's own templates directory.
+pub const PROJECT_TEMPLATES: &str = "templates";
+
+/// Partials (`partials/<name>.hbs`) are registered with their file name, usable as `{{> name}}`.
+const PARTIALS_DIR: &str = "partials";
+
+/// The directories templates are looked up in, from highest to lowest priority.
+pub struct Templates {
+    dirs: Vec<PathBuf>,
+}
+
+impl Templates {
+    pub fn new(theme: Option<&str>) -> Result<Self> {
+        let mut dirs = vec![PathBuf::from(PROJECT_TEMPLATES)];
+        if let Some(theme) = theme {
+            crate::theme::load(theme)?;
+            dirs.push(theme_dir(theme));
+        }
+        dirs.push(CONFIG_PATH.join("templates"));
+        Ok(Templates { dirs })
+    }
+
+    /// Every directory templates are looked up in (even if it doesn't exist), from highest to lowest priority.
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Reads the template at `name` (e.g. `routing/src/main.rs.hbs`).
+    pub fn read(&self, name: &str) -> Result<Cow<'static, str>> {
+        if let Some(path) = self.find(name) {
+            return fs::read_to_string(&path)
+                .map(Cow::Owned)
+                .with_context(|| format!("Couldn't read template `{}`", path.display()));
+        }
+
+        DEFAULT_TEMPLATES
+            .iter()
+            .find(|(default, _)| *default == name)
+            .map(|(_, content)| Cow::Borrowed(*content))
+            .with_context(|| format!("Couldn't find template `{name}`"))
+    }
+
+    /// The first file at `name` in the lookup directories.
+    fn find(&self, name: &str) -> Option<PathBuf> {
+        self.dirs
+            .iter()
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+    }
+
+    /// The built-in styles (file name and contents), copied to the output's `styles` directory.
+    pub fn styles(&self) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut styles: HashMap<String, Vec<u8>> = DEFAULT_TEMPLATES
+            .iter()
+            .filter_map(|(name, content)| {
+                name.strip_prefix("styles/")
+                    .map(|file| (file.to_string(), content.as_bytes().to_vec()))
+            })
+            .collect();
+
+        for (name, path) in self.files_in("styles") {
+            styles.insert(
+                name,
+                fs::read(&path).with_context(|| format!("Couldn't read `{}`", path.display()))?,
+            );
+        }
+
+        let mut styles: Vec<(String, Vec<u8>)> = styles.into_iter().collect();
+        styles.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(styles)
+    }
+
+    /// Every partial (name and contents), from `partials/*.hbs`.
+    pub fn partials(&self) -> Result<Vec<(String, String)>> {
+        let mut partials = Vec::new();
+        for (name, path) in self.files_in(PARTIALS_DIR) {
+            let Some(name) = name.strip_suffix(".hbs") else {
+                continue;
+            };
+            partials.push((
+                name.to_string(),
+                fs::read_to_string(&path)
+                    .with_context(|| format!("Couldn't read partial `{}`", path.display()))?,
+            ));
+        }
+        Ok(partials)
+    }
+
+    /// Files in the subdirectory `dir` of every lookup directory, by their path relative to it. If more than one directory has the same file, the one with the highest priority wins.
+    fn files_in(&self, dir: &str) -> Vec<(String, PathBuf)> {
+        let mut files: HashMap<String, PathBuf> = HashMap::new();
+        for base in self.dirs.iter().rev() {
+            let base = base.join(dir);
+            for entry in WalkDir::new(&base)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+            {
+                if let Ok(relative) = entry.path().strip_prefix(&base) {
+                    files.insert(
+                        relative.to_string_lossy().replace('\\', "/"),
+                        entry.into_path(),
+                    );
+                }
+            }
+        }
+
+        let mut files: Vec<(String, PathBuf)> = files.into_iter().collect();
+        files.sort();
+        files
+    }
+}
+
+/// Where the theme `name` is installed.
+#[inline]
+pub fn theme_dir(name: &str) -> PathBuf {
+    CONFIG_PATH.join("themes").join(Path::new(name))
+}
@@ -0,0 +1,263 @@
+//! Themes installed in `CONFIG_PATH/themes/<name>`, managed with `cuteness theme`. A theme is a directory laid out like `templates` (any file it doesn't have falls back to the defaults) with a `theme.toml` manifest.
+
+use std::{fs, path::Path, process::Command};
+
+use anyhow::{bail, Context, Result};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+use crate::templates::theme_dir;
+use crate::{copy_dir, CONFIG_PATH};
+
+/// Name of the manifest file, in the theme's root directory.
+pub const MANIFEST_FILE: &str = "theme.toml";
+
+/// `theme.toml`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Manifest {
+    /// Name the theme is installed (and selected) with
+    pub name: String,
+    pub version: String,
+    /// Versions of cuteness the theme works with, e.g. `">=0.8, <0.9"`
+    pub cuteness: String,
+}
+
+impl Manifest {
+    /// Reads the manifest of the theme in `dir`.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let manifest: Manifest = toml::from_str(
+            &fs::read_to_string(&path)
+                .with_context(|| format!("Couldn't read `{}`", path.display()))?,
+        )
+        .with_context(|| format!("Couldn't parse `{}`", path.display()))?;
+
+        check_name(&manifest.name).with_context(|| format!("Invalid `{}`", path.display()))?;
+        Ok(manifest)
+    }
+
+    /// Fails if the theme doesn't support this version of cuteness.
+    pub fn check_compatible(&self) -> Result<()> {
+        let requirement = VersionReq::parse(&self.cuteness).with_context(|| {
+            format!(
+                "Theme `{}` requires an invalid cuteness version `{}`",
+                self.name, self.cuteness
+            )
+        })?;
+        let current = Version::parse(env!("CARGO_PKG_VERSION")).unwrap();
+        if !requirement.matches(&current) {
+            bail!(
+                "Theme `{}` {} requires cuteness {}, but this is cuteness {current}",
+                self.name,
+                self.version,
+                self.cuteness
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Theme names are used as directory names, so they can't contain anything else than letters, numbers, `-` and `_`.
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!("Invalid theme name `{name}` (only letters, numbers, `-` and `_` are allowed)");
+    }
+    Ok(())
+}
+
+/// Loads the manifest of the installed theme `name`, checking it can be used with this version of cuteness.
+pub fn load(name: &str) -> Result<Manifest> {
+    check_name(name)?;
+    let dir = theme_dir(name);
+    if !dir.exists() {
+        bail!(
+            "Theme `{name}` isn't installed (couldn't find `{}`), install it with `cuteness theme add`",
+            dir.display()
+        );
+    }
+
+    let manifest = Manifest::load(&dir)?;
+    manifest.check_compatible()?;
+    Ok(manifest)
+}
+
+/// Every installed theme, sorted by name. Directories that aren't valid themes are skipped with a warning.
+pub fn list() -> Result<Vec<Manifest>> {
+    let themes_dir = CONFIG_PATH.join("themes");
+    if !themes_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut themes = Vec::new();
+    for entry in fs::read_dir(&themes_dir)
+        .with_context(|| format!("Couldn't read directory `{}`", themes_dir.display()))?
+    {
+        let entry = entry.context("Couldn't read theme directory")?;
+        // Hidden directories, like a `.staging` left by an interrupted `theme add`, aren't themes
+        if !entry.path().is_dir() || entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        match Manifest::load(&entry.path()) {
+            Ok(manifest) => themes.push(manifest),
+            Err(e) => eprintln!(
+                "Warning: skipping `{}`, it isn't a valid theme: {e:#}",
+                entry.path().display()
+            ),
+        }
+    }
+    themes.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(themes)
+}
+
+/// Installs the theme at `source`, either a local directory or a git repository url.
+pub fn add(source: &str) -> Result<Manifest> {
+    let themes_dir = CONFIG_PATH.join("themes");
+    fs::create_dir_all(&themes_dir)
+        .with_context(|| format!("Couldn't create directory `{}`", themes_dir.display()))?;
+
+    // Fetched into a temporary directory first, the theme's name is only known after reading its manifest
+    let staging = themes_dir.join(".staging");
+    if staging.exists() {
+        fs::remove_dir_all(&staging)
+            .with_context(|| format!("Couldn't remove directory `{}`", staging.display()))?;
+    }
+
+    let result = fetch(source, &staging).and_then(|()| {
+        let manifest = Manifest::load(&staging)?;
+        manifest.check_compatible()?;
+
+        let destination = theme_dir(&manifest.name);
+        if destination.exists() {
+            bail!(
+                "Theme `{}` is already installed, remove it first with `cuteness theme remove {}`",
+                manifest.name,
+                manifest.name
+            );
+        }
+        fs::rename(&staging, &destination)
+            .with_context(|| format!("Couldn't move the theme to `{}`", destination.display()))?;
+        Ok(manifest)
+    });
+
+    if staging.exists() {
+        let _ = fs::remove_dir_all(&staging);
+    }
+    result
+}
+
+/// Copies (or clones) `source` into `destination`.
+fn fetch(source: &str, destination: &Path) -> Result<()> {
+    let local = Path::new(source);
+    if local.is_dir() {
+        return copy_dir(local, destination);
+    }
+
+    let status = Command::new("git")
+        .args(["clone", "--depth=1", source])
+        .arg(destination)
+        .status()
+        .context("Couldn't run `git`")?;
+    if !status.success() {
+        bail!("Couldn't clone `{source}` (`git clone` exited with {status})");
+    }
+
+    // The history isn't needed, and would be hashed on every build
+    fs::remove_dir_all(destination.join(".git")).context("Couldn't remove the theme's `.git`")
+}
+
+/// Selects the installed theme `name` in the project's `cuteconfig.toml` (`[misc] theme`), keeping the rest of the file as it is.
+pub fn select(name: &str) -> Result<()> {
+    load(name)?;
+
+    let config = fs::read_to_string("cuteconfig.toml")
+        .context("Couldn't read `cuteconfig.toml`, is this a cuteness project?")?;
+    let mut document: toml_edit::Document =
+        config.parse().context("Couldn't parse `cuteconfig.toml`")?;
+
+    if !document.contains_table("misc") {
+        document["misc"] = toml_edit::table();
+    }
+    document["misc"]["theme"] = toml_edit::value(name);
+
+    fs::write("cuteconfig.toml", document.to_string()).context("Couldn't write `cuteconfig.toml`")
+}
+
+/// The theme selected in the current project's `cuteconfig.toml`, if this is a project and it selects one.
+fn selected() -> Result<Option<String>> {
+    if !Path::new("cuteconfig.toml").exists() {
+        return Ok(None);
+    }
+    let config =
+        fs::read_to_string("cuteconfig.toml").context("Couldn't read `cuteconfig.toml`")?;
+    let document: toml_edit::Document =
+        config.parse().context("Couldn't parse `cuteconfig.toml`")?;
+    Ok(document
+        .get("misc")
+        .and_then(|misc| misc.get("theme"))
+        .and_then(|theme| theme.as_str())
+        .map(str::to_string))
+}
+
+/// Uninstalls the theme `name`, unless the current project uses it.
+pub fn remove(name: &str) -> Result<()> {
+    check_name(name)?;
+    let dir = theme_dir(name);
+    if !dir.exists() {
+        bail!("Theme `{name}` isn't installed");
+    }
+    if selected()?.as_deref() == Some(name) {
+        bail!(
+            "Theme `{name}` is selected in `cuteconfig.toml` (`[misc] theme`), select another one with `cuteness theme use` or remove the setting first"
+        );
+    }
+    fs::remove_dir_all(&dir)
+        .with_context(|| format!("Couldn't remove directory `{}`", dir.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(cuteness: &str) -> Manifest {
+        Manifest {
+            name: "my-theme".to_string(),
+            version: "1.0.0".to_string(),
+            cuteness: cuteness.to_string(),
+        }
+    }
+
+    #[test]
+    fn valid_names() {
+        assert!(check_name("my-theme").is_ok());
+        assert!(check_name("Theme_2").is_ok());
+    }
+
+    #[test]
+    fn invalid_names() {
+        assert!(check_name("").is_err());
+        assert!(check_name("../templates").is_err());
+        assert!(check_name("my theme").is_err());
+        assert!(check_name(".hidden").is_err());
+    }
+
+    #[test]
+    fn compatible_versions() {
+        let current = env!("CARGO_PKG_VERSION");
+        assert!(manifest("*").check_compatible().is_ok());
+        assert!(manifest(&format!("={current}")).check_compatible().is_ok());
+        assert!(manifest(&format!(">={current}, <1000"))
+            .check_compatible()
+            .is_ok());
+    }
+
+    #[test]
+    fn incompatible_versions() {
+        assert!(manifest("<0.0.1").check_compatible().is_err());
+        assert!(manifest(">=1000").check_compatible().is_err());
+        assert!(manifest("not a version").check_compatible().is_err());
+    }
+}
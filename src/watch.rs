@@ -0,0 +1,82 @@
+//! File watching for `cuteness serve` and `cuteness build --watch`.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, RecvTimeoutError},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::templates::PROJECT_TEMPLATES;
+
+/// Editors usually write a file in several steps (truncate, write, rename...), so we wait this long after the last event before reporting a change.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// Files in the project's root directory that affect the build.
+const ROOT_FILES: [&str; 2] = ["SUMMARY.toml", "cuteconfig.toml"];
+
+/// Watches `src/`, `templates/`, `SUMMARY.toml` and `cuteconfig.toml`, calling `on_change` with every batch of changed paths. This function never returns unless the watcher fails.
+pub fn watch<F>(mut on_change: F) -> Result<()>
+where
+    F: FnMut(&[PathBuf]),
+{
+    let (tx, rx) = channel();
+    let mut watcher = notify::recommended_watcher(tx).context("Couldn't create file watcher")?;
+
+    watcher
+        .watch(Path::new("src"), RecursiveMode::Recursive)
+        .context("Couldn't watch directory `src`")?;
+    // Project templates are optional, a `templates` directory created later is only picked up on restart.
+    if Path::new(PROJECT_TEMPLATES).is_dir() {
+        watcher
+            .watch(Path::new(PROJECT_TEMPLATES), RecursiveMode::Recursive)
+            .context("Couldn't watch directory `templates`")?;
+    }
+    // Watching the root files directly would lose track of them as soon as an editor replaces them, so we watch the directory and filter.
+    watcher
+        .watch(Path::new("."), RecursiveMode::NonRecursive)
+        .context("Couldn't watch the current directory")?;
+
+    let root = std::env::current_dir().context("Couldn't get current directory")?;
+    let mut changed: Vec<PathBuf> = Vec::new();
+
+    loop {
+        let event = if changed.is_empty() {
+            rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            rx.recv_timeout(DEBOUNCE)
+        };
+
+        match event {
+            Ok(event) => {
+                let event = event.context("File watcher error")?;
+                if matches!(event.kind, EventKind::Access(_)) {
+                    continue;
+                }
+                for path in event.paths {
+                    let path = path.strip_prefix(&root).unwrap_or(&path).to_path_buf();
+                    if is_relevant(&path) && !changed.contains(&path) {
+                        changed.push(path);
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                on_change(&changed);
+                changed.clear();
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                anyhow::bail!("File watcher stopped unexpectedly")
+            }
+        }
+    }
+}
+
+#[inline]
+fn is_relevant(path: &Path) -> bool {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    path.starts_with("src")
+        || path.starts_with(PROJECT_TEMPLATES)
+        || ROOT_FILES.iter().any(|file| path.as_os_str() == *file)
+}